@@ -1,61 +1,335 @@
-use crate::error::ClientError as Error;
+pub mod migration;
 
-use std::collections::HashMap;
+use crate::error::{ClientError as Error, SignedRecordError, StateError};
+use crate::state::{ClientState, StateStore};
+use crate::utils::now;
+
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
 
 use crate::transport::{
     auth::Auth,
-    crypto,
-    http::{request, HeaderMap, Method, Url},
-    resolver::Resolver,
+    challenge::ChallengePolicy,
+    cookies::{SessionCookie, SessionStore},
+    crypto::{
+        self, encryption,
+        mnemonic::{mnemonic_to_seed, seed_to_mnemonic},
+        sharing,
+        signed::{record_path, SignedRecord},
+        DeterministicKeyGen,
+    },
+    http::{Bytes, HeaderMap, HttpClient, HttpConfig, Method, Url},
+    resolver::{
+        cache::{CacheStrategy, ResolverCache, MAX_TTL},
+        Resolver,
+    },
+    session::Session,
 };
 
+/// Options for listing entries under a path in user's repository
+///
+/// `cursor` continues listing from where the previous page ended, while `offset` skips a number of
+/// entries from the beginning of the listing.
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    pub reverse: bool,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+/// Single entry of a repository listing
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ListEntry {
+    pub path: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub updated_at: Option<u64>,
+}
+
+/// Page of a repository listing, `cursor` is set if there are more entries to fetch
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ListPage {
+    pub entries: Vec<ListEntry>,
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+/// Options for range queries over keys in user's repository
+///
+/// `start` is inclusive and `end` is exclusive, both bounds are optional.
+#[derive(Debug, Clone, Default)]
+pub struct QueryOptions {
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub limit: Option<usize>,
+    pub reverse: bool,
+}
+
+/// Key/value pair returned by a repository query
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct QueryEntry {
+    pub key: String,
+    pub value: String,
+}
+
 /// This is the pubky client class. It is used for accessing pubky infrastructure for CRUD options
 /// over user's data in pubky network.
 ///
 /// Client accepts optional seed for pubky key generation.
 /// It accepts optional homeserver URL and relay URL.
 ///
-/// It has as a cache which matches {userId:(homeserver_url, sesison_id)}.
+/// It has as a cache which matches {userId:homeserver_url}. Homeservers of other users are
+/// discovered on first access to their data. Sessions are kept in a [`SessionStore`] keyed by
+/// homeserver and user.
 ///
 /// It has encapsulates an instance of a resolver to publish user's identity to the network, as
-/// well as to lookup other user's homeservers
+/// well as to lookup other user's homeservers. The resolver and its cache are shared by the
+/// homeservers of all users, which are looked up again once their records expire.
 ///
 ///
 /// The CRUD operations for homeserver are performed using http requests.
-
 pub struct Client<'a> {
-    pub homeserver_url: Url, // own homeserver
-    pub user_id: String,     // own user id
+    pub homeserver_url: Option<Url>, // own homeserver
+    pub user_id: String,             // own user id
     seed: [u8; 32],
     homeservers_cache: HashMap<String, Auth<'a>>, // homervers of others
     dht_relay: Option<&'a Url>,
+    resolver: Resolver<'a>,
+    http: HttpClient,
+    challenge_policy: ChallengePolicy,
 }
 
-impl Client<'_> {
-    pub fn new<'a>(
-        seed: Option<[u8; 32]>,
-        homeserver_url: Option<Url>,
-        dht_relay: Option<&'a Url>,
-        bootstrap: Option<&'a Vec<String>>,
-    ) -> Client<'a> {
-        let seed = seed.unwrap_or(crypto::random_bytes(32).try_into().unwrap());
+/// Builder for the pubky client.
+///
+/// Nothing is sent over the network while building, unless signup or login was requested. If the
+/// homeserver URL is not provided, it is resolved on first use.
+#[derive(Default)]
+pub struct ClientBuilder<'a> {
+    seed: Option<[u8; 32]>,
+    mnemonic: Option<String>,
+    homeserver_url: Option<Url>,
+    dht_relay: Option<&'a Url>,
+    bootstrap: Option<&'a Vec<String>>,
+    cache_strategy: CacheStrategy,
+    resolver_cache: Option<ResolverCache>,
+    http_config: HttpConfig,
+    challenge_policy: ChallengePolicy,
+    sessions: Option<SessionStore>,
+    state: Option<ClientState>,
+    connect: Connect,
+}
+
+#[derive(Default)]
+pub(crate) enum Connect {
+    #[default]
+    None,
+    Signup,
+    Login,
+}
+
+impl<'a> ClientBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seed for pubky key generation, random seed is used if neither seed nor keypair is set
+    pub fn seed(mut self, seed: [u8; 32]) -> Self {
+        self.seed = Some(seed);
+        self.mnemonic = None;
+        self
+    }
+
+    /// Use existing keypair instead of the seed
+    pub fn keypair(mut self, keypair: &crypto::Keypair) -> Self {
+        self.seed = Some(keypair.secret_key());
+        self.mnemonic = None;
+        self
+    }
+
+    /// Recover the seed from a recovery phrase, see [`Client::mnemonic`]
+    pub fn mnemonic(mut self, phrase: &str) -> Self {
+        self.mnemonic = Some(phrase.to_string());
+        self.seed = None;
+        self
+    }
+
+    /// Homeserver of the user, skips homeserver resolution
+    pub fn homeserver_url(mut self, homeserver_url: Url) -> Self {
+        self.homeserver_url = Some(homeserver_url);
+        self
+    }
+
+    /// Relay used for publishing and resolving instead of DHT
+    pub fn dht_relay(mut self, dht_relay: &'a Url) -> Self {
+        self.dht_relay = Some(dht_relay);
+        self
+    }
+
+    /// Bootstrap nodes of the DHT
+    pub fn bootstrap(mut self, bootstrap: &'a Vec<String>) -> Self {
+        self.bootstrap = Some(bootstrap);
+        self
+    }
+
+    /// How lookups of homeservers use the resolver cache, read-through by default
+    pub fn cache_strategy(mut self, cache_strategy: CacheStrategy) -> Self {
+        self.cache_strategy = cache_strategy;
+        self
+    }
+
+    /// Cache of resolved homeservers, e.g. one with a [`PacketStore`] to resolve them without the
+    /// network after a restart
+    ///
+    /// [`PacketStore`]: crate::transport::resolver::store::PacketStore
+    pub fn resolver_cache(mut self, resolver_cache: ResolverCache) -> Self {
+        self.resolver_cache = Some(resolver_cache);
+        self
+    }
+
+    /// Timeout of a single request to a homeserver
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.http_config.timeout = Some(timeout);
+        self
+    }
+
+    /// Tolerated difference between the client and homeserver clocks when checking the
+    /// expiration of auth challenges
+    pub fn clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.challenge_policy.clock_skew = clock_skew;
+        self
+    }
+
+    /// Configuration of the HTTP connection pool shared by all requests of the client
+    pub fn http_config(mut self, http_config: HttpConfig) -> Self {
+        self.http_config = http_config;
+        self
+    }
+
+    /// Session store shared with other clients, e.g. to be logged in to several accounts at once
+    pub fn sessions(mut self, sessions: SessionStore) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// Restore homeservers and sessions saved by [`Client::save_state`], the seed has to belong
+    /// to the same user
+    pub fn state(mut self, state: ClientState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Sign up at the homeserver while building the client
+    pub fn signup(mut self) -> Self {
+        self.connect = Connect::Signup;
+        self
+    }
+
+    /// Login to the homeserver while building the client
+    pub fn login(mut self) -> Self {
+        self.connect = Connect::Login;
+        self
+    }
+
+    pub fn build(self) -> Result<Client<'a>, Error> {
+        let seed = match &self.mnemonic {
+            Some(phrase) => mnemonic_to_seed(phrase).map_err(Error::InvalidMnemonic)?,
+            None => self
+                .seed
+                .unwrap_or_else(|| crypto::random_bytes(32).try_into().unwrap()),
+        };
+        let user_id = crypto::Keypair::generate(Some(&seed)).to_z32();
 
-        let resolver = Resolver::new(dht_relay, bootstrap);
-        let mut auth = Auth::new(resolver, homeserver_url);
+        let mut http = HttpClient::new(&self.http_config).map_err(Error::InvalidHttpConfig)?;
+        if let Some(sessions) = self.sessions {
+            http = http.with_sessions(sessions);
+        }
+
+        let mut resolver =
+            Resolver::new(self.dht_relay, self.bootstrap).with_strategy(self.cache_strategy);
+        if let Some(cache) = self.resolver_cache {
+            resolver = resolver.with_cache(cache);
+        }
+
+        let mut homeservers = match &self.state {
+            Some(state) => restore_state(state, &user_id, resolver.cache(), http.sessions())?,
+            None => HashMap::new(),
+        };
 
-        let user_id = auth.signup(&seed, None).unwrap();
-        let homeserver_url = auth.homeserver_url.clone().unwrap();
+        let mut auth = Auth::new(resolver.clone(), http.clone(), self.homeserver_url);
+        if auth.homeserver_url.is_none() {
+            auth.homeserver_url = homeservers.remove(&user_id);
+        }
+        auth.challenge_policy = self.challenge_policy;
+        if self.state.is_some() {
+            // Restored session is only checked on the next call to the homeserver
+            auth.user_id = Some(user_id.clone());
+        }
 
+        match self.connect {
+            Connect::None => (),
+            Connect::Signup => {
+                auth.signup(&seed, self.dht_relay)
+                    .map_err(Error::FailedToSignup)?;
+            }
+            Connect::Login => {
+                auth.login(&seed, self.dht_relay)
+                    .map_err(Error::FailedToLogin)?;
+            }
+        };
+
+        let homeserver_url = auth.homeserver_url.clone();
         let mut homeservers_cache = HashMap::new();
         homeservers_cache.insert(user_id.clone(), auth);
+        for (id, url) in homeservers {
+            let mut auth = Auth::new(resolver.clone(), http.clone(), None);
+            auth.homeserver_url = Some(url);
+            homeservers_cache.insert(id, auth);
+        }
 
-        Client {
+        Ok(Client {
             seed,
             homeservers_cache,
             homeserver_url,
             user_id,
-            dht_relay,
+            dht_relay: self.dht_relay,
+            resolver,
+            http,
+            challenge_policy: self.challenge_policy,
+        })
+    }
+}
+
+impl<'a> Client<'a> {
+    /// Create a client and sign up at the homeserver
+    pub fn new(
+        seed: Option<[u8; 32]>,
+        homeserver_url: Option<Url>,
+        dht_relay: Option<&'a Url>,
+        bootstrap: Option<&'a Vec<String>>,
+    ) -> Result<Client<'a>, Error> {
+        let mut builder = ClientBuilder::new().signup();
+
+        if let Some(seed) = seed {
+            builder = builder.seed(seed);
         }
+        if let Some(homeserver_url) = homeserver_url {
+            builder = builder.homeserver_url(homeserver_url);
+        }
+        if let Some(dht_relay) = dht_relay {
+            builder = builder.dht_relay(dht_relay);
+        }
+        if let Some(bootstrap) = bootstrap {
+            builder = builder.bootstrap(bootstrap);
+        }
+
+        builder.build()
+    }
+
+    pub fn builder() -> ClientBuilder<'a> {
+        ClientBuilder::new()
     }
 
     /* GENERAL LOGIC */
@@ -71,6 +345,22 @@ impl Client<'_> {
         keypair.to_z32()
     }
 
+    /// Derive the keypair of an application from the root seed, e.g. `app:chat/v1`
+    pub fn derive_keypair(&self, label: &str) -> crypto::Keypair {
+        crypto::derive_keypair(&self.seed, label)
+    }
+
+    /// Derive the seed of an application, e.g. to build a client acting as the application's own
+    /// identity without handing it the root seed
+    pub fn derive_seed(&self, label: &str) -> [u8; 32] {
+        crypto::derive_seed(&self.seed, label)
+    }
+
+    /// Recovery phrase of the seed, restores the client with [`ClientBuilder::mnemonic`]
+    pub fn mnemonic(&self) -> String {
+        seed_to_mnemonic(&self.seed)
+    }
+
     /* "AUTH" RELATED LOGIC */
     /// login
     pub fn login(&mut self) -> Result<String, Error> {
@@ -99,7 +389,7 @@ impl Client<'_> {
     }
 
     /// session
-    pub fn session(&mut self) -> Result<String, Error> {
+    pub fn session(&mut self) -> Result<Option<Session>, Error> {
         match self
             .homeservers_cache
             .get_mut(&self.user_id)
@@ -111,27 +401,61 @@ impl Client<'_> {
         }
     }
 
+    /// All live sessions of this client, including sessions of other users sharing its session
+    /// store
+    pub fn sessions(&self) -> Vec<SessionCookie> {
+        self.http.sessions().list()
+    }
+
+    /// Revoke the session of the user at the homeserver and return the revoked session id
+    pub fn revoke_session(&mut self, homeserver_url: &Url, user_id: &str) -> Result<String, Error> {
+        let mut auth = Auth::new(
+            self.resolver.clone(),
+            self.http.clone(),
+            Some(homeserver_url.clone()),
+        );
+
+        auth.logout(user_id).map_err(Error::FailedToLogout)
+    }
+
+    /// Snapshot of the resolved homeservers and sessions of the client
+    pub fn state(&self) -> ClientState {
+        let mut state = ClientState::new(self.user_id.clone());
+
+        for (user_id, auth) in &self.homeservers_cache {
+            if let Some(url) = &auth.homeserver_url {
+                state.homeservers.insert(user_id.clone(), url.to_string());
+            }
+            let entry = crypto::PublicKey::try_from(user_id.as_str())
+                .ok()
+                .and_then(|public_key| self.resolver.cache().entry(&public_key));
+            if let Some(entry) = entry {
+                state.expires_at.insert(user_id.clone(), entry.expires_at);
+            }
+        }
+        state.sessions = self.http.sessions().list();
+
+        state
+    }
+
+    /// Save the client state, so it can be restored with [`ClientBuilder::state`] after a restart
+    pub fn save_state(&self, store: &impl StateStore) -> Result<(), Error> {
+        store.save(&self.state()).map_err(Error::FailedToSaveState)
+    }
+
     /* "REPOS" RELATED LOGIC */
 
     /// Create repository for user
     pub fn create(&mut self, user_id: &str, repo_name: &str) -> Result<(), Error> {
         let url = &self
-            .homeservers_cache
-            .get(user_id)
-            .unwrap()
-            .homeserver_url
-            .clone()
-            .unwrap()
+            .homeserver(user_id)?
             .join(&format!("/mvp/users/{}/repos/{}", user_id, repo_name))
             .unwrap();
 
-        match request(
-            Method::PUT,
-            url.clone(),
-            &mut self.homeservers_cache.get_mut(user_id).unwrap().session_id,
-            None,
-            None,
-        ) {
+        match self
+            .http
+            .request(Method::PUT, url.clone(), Some(&self.user_id), None, None)
+        {
             Ok(_) => Ok(()),
             Err(e) => Err(Error::FailedToCreateRepository(e)),
         }
@@ -144,22 +468,26 @@ impl Client<'_> {
         repo_name: &str,
         path: &str,
         payload: &str,
+    ) -> Result<Url, Error> {
+        self.put_bytes(user_id, repo_name, path, payload.as_bytes())
+    }
+
+    /// Put binary data into user's repository and return URL to this repo
+    pub fn put_bytes(
+        &mut self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+        payload: &[u8],
     ) -> Result<Url, Error> {
         let url = &self
-            .homeservers_cache
-            .get(user_id)
-            .unwrap()
-            .homeserver_url
-            .clone()
-            .unwrap()
+            .homeserver(user_id)?
             .join(&format!(
                 "/mvp/users/{}/repos/{}/{}",
                 user_id, repo_name, path
             ))
             .unwrap();
 
-        println!("Calling: {}", url);
-
         let mut headers = HeaderMap::new();
         headers.insert(
             "Content-Type",
@@ -170,12 +498,12 @@ impl Client<'_> {
             payload.len().to_string().try_into().unwrap(),
         );
 
-        let response = request(
+        let response = self.http.request(
             Method::PUT,
             url.clone(),
-            &mut self.homeservers_cache.get_mut(user_id).unwrap().session_id,
+            Some(&self.user_id),
             Some(&headers),
-            Some(payload.to_string()),
+            Some(payload.to_vec()),
         );
 
         match response {
@@ -186,26 +514,29 @@ impl Client<'_> {
 
     /// Get data from user's repository and return it as a JSON(?)
     pub fn get(&mut self, user_id: &str, repo_name: &str, path: &str) -> Result<String, Error> {
+        let body = self.get_bytes(user_id, repo_name, path)?;
+
+        String::from_utf8(body.to_vec()).map_err(Error::InvalidUtf8)
+    }
+
+    /// Get binary data from user's repository
+    pub fn get_bytes(
+        &mut self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+    ) -> Result<Bytes, Error> {
         let url = &self
-            .homeservers_cache
-            .get(user_id)
-            .unwrap()
-            .homeserver_url
-            .clone()
-            .unwrap()
+            .homeserver(user_id)?
             .join(&format!(
                 "/mvp/users/{}/repos/{}/{}",
                 user_id, repo_name, path
             ))
             .unwrap();
 
-        let response = request(
-            Method::GET,
-            url.clone(),
-            &mut self.homeservers_cache.get_mut(user_id).unwrap().session_id,
-            None,
-            None,
-        );
+        let response = self
+            .http
+            .request(Method::GET, url.clone(), Some(&self.user_id), None, None);
 
         match response {
             Ok(body) => Ok(body),
@@ -213,28 +544,156 @@ impl Client<'_> {
         }
     }
 
+    /// Encrypt data with a key derived from the seed and put it into user's repository, the
+    /// homeserver only ever sees the ciphertext
+    pub fn put_encrypted(
+        &mut self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+        payload: &[u8],
+    ) -> Result<Url, Error> {
+        let mut key = encryption::derive_key(&self.seed);
+        let envelope = encryption::encrypt(
+            &key,
+            payload,
+            &encryption::record_aad(user_id, repo_name, path),
+        );
+        crypto::zeroize(&mut key);
+
+        self.put_bytes(user_id, repo_name, path, &envelope)
+    }
+
+    /// Get data stored with [`Client::put_encrypted`] and decrypt it, fails if it was tampered
+    /// with or moved to another path
+    pub fn get_decrypted(
+        &mut self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let envelope = self.get_bytes(user_id, repo_name, path)?;
+
+        let mut key = encryption::derive_key(&self.seed);
+        let payload = encryption::decrypt(
+            &key,
+            &envelope,
+            &encryption::record_aad(user_id, repo_name, path),
+        );
+        crypto::zeroize(&mut key);
+
+        payload.map_err(Error::FailedToDecryptData)
+    }
+
+    /// Encrypt data for other pubky users and put it into user's repository, the client's own
+    /// user is always one of the recipients
+    pub fn put_shared(
+        &mut self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+        payload: &[u8],
+        recipients: &[&str],
+    ) -> Result<Url, Error> {
+        let mut public_keys = vec![self.generate_keypair().public_key()];
+        for recipient in recipients {
+            let public_key = crypto::PublicKey::try_from(*recipient)
+                .map_err(|_| Error::InvalidUserId(recipient.to_string()))?;
+            if !public_keys.contains(&public_key) {
+                public_keys.push(public_key);
+            }
+        }
+
+        let envelope = sharing::seal(
+            payload,
+            &public_keys,
+            &encryption::record_aad(user_id, repo_name, path),
+        )
+        .map_err(Error::FailedToEncryptData)?;
+
+        self.put_bytes(user_id, repo_name, path, &envelope)
+    }
+
+    /// Get data shared with the client's user through [`Client::put_shared`] and decrypt it
+    pub fn get_shared(
+        &mut self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let envelope = self.get_bytes(user_id, repo_name, path)?;
+
+        sharing::open(
+            &envelope,
+            &self.generate_keypair(),
+            &encryption::record_aad(user_id, repo_name, path),
+        )
+        .map_err(Error::FailedToDecryptData)
+    }
+
+    /// Sign data with the user's keypair and put it into user's repository, so readers can check
+    /// authorship with [`Client::get_verified`]
+    pub fn put_signed(
+        &mut self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+        payload: &[u8],
+    ) -> Result<Url, Error> {
+        if user_id != self.user_id {
+            return Err(Error::FailedToSignData(SignedRecordError::NotAuthor(
+                user_id.to_string(),
+            )));
+        }
+
+        let record = SignedRecord::sign(
+            &self.generate_keypair(),
+            &record_path(repo_name, path),
+            payload,
+        )
+        .and_then(|record| record.to_bytes())
+        .map_err(Error::FailedToSignData)?;
+
+        self.put_bytes(user_id, repo_name, path, &record)
+    }
+
+    /// Get data stored with [`Client::put_signed`], verifying it was signed by the user for this
+    /// location, whatever the homeserver serving it
+    ///
+    /// Freshness is not verified: a homeserver can serve an older version that the user signed
+    /// for the same location. Compare [`SignedRecord::timestamp`] with the latest version seen to
+    /// detect a rollback.
+    pub fn get_verified(
+        &mut self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+    ) -> Result<SignedRecord, Error> {
+        let public_key = crypto::PublicKey::try_from(user_id)
+            .map_err(|_| Error::InvalidUserId(user_id.to_string()))?;
+        let body = self.get_bytes(user_id, repo_name, path)?;
+
+        let record = SignedRecord::from_bytes(&body).map_err(Error::FailedToVerifyData)?;
+        record
+            .verify(&public_key, &record_path(repo_name, path))
+            .map_err(Error::FailedToVerifyData)?;
+
+        Ok(record)
+    }
+
     /// Delete data from user's repository
     pub fn delete(&mut self, user_id: &str, repo_name: &str, path: &str) -> Result<(), Error> {
         let url = &self
-            .homeservers_cache
-            .get(user_id)
-            .unwrap()
-            .homeserver_url
-            .clone()
-            .unwrap()
+            .homeserver(user_id)?
             .join(&format!(
                 "/mvp/users/{}/repos/{}/{}",
                 user_id, repo_name, path
             ))
             .unwrap();
 
-        let response = request(
-            Method::DELETE,
-            url.clone(),
-            &mut self.homeservers_cache.get_mut(user_id).unwrap().session_id,
-            None,
-            None,
-        );
+        let response =
+            self.http
+                .request(Method::DELETE, url.clone(), Some(&self.user_id), None, None);
 
         match response {
             Ok(_) => Ok(()),
@@ -242,94 +701,342 @@ impl Client<'_> {
         }
     }
 
-    //     /// List data in user's repository
-    //     /*
-    //     ListOption {
-    //          reverse: bool,
-    //          offset: usize,
-    //          limit: usize,
-    //     }
-    //     */
-    //     pub fn list (&mut self, user_id: &str, repo_name: &str, path: &str, opts: Option<ListOption>) -> Result<Vec<String>, String> { }
-    //
-    //
-    //     /// Query data in user's repository
-    //     /*
-    //     // Maybe can repurpose ListOption
-    //     QueryOptions {
-    //          reverse: bool,
-    //          start: usize,
-    //          end: usize,
-    //          limit: usize,
-    //          reverse: bool,
-    //     }
-    //     */
-    //     pub fn query (&mut self, user_id: &str, repo_name: &str, query: Option<QueryOptions>) -> Result<Vec<String>, String> { }
-}
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::test_utils::*;
-    use crate::transport::crypto::{DeterministicKeyGen, Keypair};
-    use mainline::dht::Testnet;
+    /// List data in user's repository under given path, returning a single page of entries
+    pub fn list(
+        &mut self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+        opts: Option<ListOptions>,
+    ) -> Result<ListPage, Error> {
+        let url = list_url(
+            &self.homeserver(user_id)?,
+            user_id,
+            repo_name,
+            path,
+            &opts.unwrap_or_default(),
+        );
 
-    #[test]
-    fn test_client_new() {
-        let testnet = Testnet::new(10);
-        let seed = b"it is a seed for key generation!";
+        let response = self
+            .http
+            .request(Method::GET, url, Some(&self.user_id), None, None);
 
-        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
-        let user_id = key_pair.to_z32();
-        let server = create_homeserver_mock(
-            user_id.to_string(),
-            "repo_name".to_string(),
-            "folder_path".to_string(),
-            "data".to_string(),
-        );
-        let _ = publish_url(
-            &key_pair,
-            &Url::parse(&server.url()).unwrap(),
-            &testnet.bootstrap,
-        );
+        match response {
+            Ok(body) => {
+                serde_json::from_slice(&body).map_err(|e| Error::InvalidResponse(e.to_string()))
+            }
+            Err(e) => Err(Error::FailedToListData(e)),
+        }
+    }
 
-        let client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap));
+    /// Iterate over all entries under given path in user's repository, fetching pages lazily
+    ///
+    /// `opts.limit` is used as a page size, `opts.offset` only applies to the first page.
+    pub fn list_iter<'c>(
+        &'c mut self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+        opts: Option<ListOptions>,
+    ) -> ListIter<'c, 'a> {
+        ListIter {
+            client: self,
+            user_id: user_id.to_string(),
+            repo_name: repo_name.to_string(),
+            path: path.to_string(),
+            opts: opts.unwrap_or_default(),
+            buffer: VecDeque::new(),
+            done: false,
+        }
+    }
 
-        assert_eq!(client.homeservers_cache.len(), 1);
-        assert_eq!(
-            client
-                .homeservers_cache
-                .get(&user_id)
-                .unwrap()
-                .homeserver_url,
-            Some(Url::parse(&server.url()).unwrap())
-        );
-        assert_eq!(
-            client.homeservers_cache.get(&user_id).unwrap().session_id,
-            Some("send_signature_signup".to_string())
+    /// Query key/value pairs in user's repository within the given key range
+    pub fn query(
+        &mut self,
+        user_id: &str,
+        repo_name: &str,
+        opts: Option<QueryOptions>,
+    ) -> Result<Vec<QueryEntry>, Error> {
+        let url = query_url(
+            &self.homeserver(user_id)?,
+            user_id,
+            repo_name,
+            &opts.unwrap_or_default(),
         );
+
+        let response = self
+            .http
+            .request(Method::GET, url, Some(&self.user_id), None, None);
+
+        match response {
+            Ok(body) => {
+                serde_json::from_slice(&body).map_err(|e| Error::InvalidResponse(e.to_string()))
+            }
+            Err(e) => Err(Error::FailedToQueryData(e)),
+        }
     }
 
-    #[test]
-    fn test_client_create() {
-        let seed = b"it is a seed for key generation!";
-        let testnet = Testnet::new(10);
+    /// Forget the homeserver of the user, so it is looked up again on next access, e.g. after the
+    /// user moved to another homeserver
+    ///
+    /// The own homeserver given to the builder is kept.
+    pub fn invalidate(&mut self, user_id: &str) -> Result<(), Error> {
+        let public_key = crypto::PublicKey::try_from(user_id)
+            .map_err(|_| Error::InvalidUserId(user_id.to_string()))?;
+        self.resolver.invalidate(&public_key);
+        if user_id != self.user_id {
+            self.homeservers_cache.remove(user_id);
+        }
 
-        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
-        let user_id = key_pair.to_z32();
-        let repo_name = "test_repo";
+        Ok(())
+    }
 
-        let server = create_homeserver_mock(
-            user_id.to_string(),
-            repo_name.to_string(),
-            "folder_path".to_string(),
-            "data".to_string(),
+    /// Get homeserver of the user, resolving it if it is not known yet
+    ///
+    /// Users other than the own one get an unauthenticated entry in the homeservers cache, once
+    /// their homeserver is resolved.
+    fn homeserver(&mut self, user_id: &str) -> Result<Url, Error> {
+        let public_key = crypto::PublicKey::try_from(user_id)
+            .map_err(|_| Error::InvalidUserId(user_id.to_string()))?;
+
+        let url = match self.homeservers_cache.get_mut(user_id) {
+            Some(auth) => auth.resolve_homeserver(&public_key, self.dht_relay),
+            None => {
+                let mut auth = Auth::new(self.resolver.clone(), self.http.clone(), None);
+                let url = auth.resolve_homeserver(&public_key, self.dht_relay);
+                if url.is_ok() {
+                    self.homeservers_cache.insert(user_id.to_string(), auth);
+                }
+                url
+            }
+        }
+        .map_err(Error::FailedToResolveHomeserver)?;
+
+        if user_id == self.user_id {
+            self.homeserver_url = Some(url.clone());
+        }
+
+        Ok(url)
+    }
+}
+
+/// Restores the sessions of the state and caches its homeservers until they expire, then they
+/// are resolved again. Returns the restored homeservers by user id
+pub(crate) fn restore_state(
+    state: &ClientState,
+    user_id: &str,
+    cache: &ResolverCache,
+    sessions: &SessionStore,
+) -> Result<HashMap<String, Url>, Error> {
+    if state.user_id != user_id {
+        return Err(Error::FailedToRestoreState(StateError::InvalidState(
+            format!("state belongs to another user {}", state.user_id),
+        )));
+    }
+    let invalid = |e: String| Error::FailedToRestoreState(StateError::InvalidState(e));
+
+    let mut homeservers = HashMap::new();
+    for (id, url) in &state.homeservers {
+        let public_key =
+            crypto::PublicKey::try_from(id.as_str()).map_err(|e| invalid(e.to_string()))?;
+        let url = Url::parse(url).map_err(|e| invalid(e.to_string()))?;
+        let ttl = match state.expires_at.get(id) {
+            Some(expires_at) => Duration::from_secs(expires_at.saturating_sub(now())),
+            None => MAX_TTL,
+        };
+        cache.insert(&public_key, url.clone(), ttl);
+        homeservers.insert(id.clone(), url);
+    }
+    for cookie in &state.sessions {
+        sessions.insert(cookie.clone());
+    }
+
+    Ok(homeservers)
+}
+
+/// Builds URL for listing entries under the path in user's repository
+pub(crate) fn list_url(
+    homeserver_url: &Url,
+    user_id: &str,
+    repo_name: &str,
+    path: &str,
+    opts: &ListOptions,
+) -> Url {
+    let path = path.trim_matches('/');
+    let folder = if path.is_empty() {
+        format!("/mvp/users/{}/repos/{}/", user_id, repo_name)
+    } else {
+        format!("/mvp/users/{}/repos/{}/{}/", user_id, repo_name, path)
+    };
+
+    let mut url = homeserver_url.join(&folder).unwrap();
+
+    {
+        let mut query = url.query_pairs_mut();
+        if opts.reverse {
+            query.append_pair("reverse", "true");
+        }
+        if let Some(offset) = opts.offset {
+            query.append_pair("offset", &offset.to_string());
+        }
+        if let Some(limit) = opts.limit {
+            query.append_pair("limit", &limit.to_string());
+        }
+        if let Some(cursor) = &opts.cursor {
+            query.append_pair("cursor", cursor);
+        }
+    }
+    if url.query() == Some("") {
+        url.set_query(None);
+    }
+
+    url
+}
+
+/// Builds URL for range query over keys in user's repository
+pub(crate) fn query_url(
+    homeserver_url: &Url,
+    user_id: &str,
+    repo_name: &str,
+    opts: &QueryOptions,
+) -> Url {
+    let mut url = homeserver_url
+        .join(&format!("/mvp/users/{}/repos/{}", user_id, repo_name))
+        .unwrap();
+
+    {
+        let mut query = url.query_pairs_mut();
+        if let Some(start) = &opts.start {
+            query.append_pair("start", start);
+        }
+        if let Some(end) = &opts.end {
+            query.append_pair("end", end);
+        }
+        if let Some(limit) = opts.limit {
+            query.append_pair("limit", &limit.to_string());
+        }
+        if opts.reverse {
+            query.append_pair("reverse", "true");
+        }
+    }
+    if url.query() == Some("") {
+        url.set_query(None);
+    }
+
+    url
+}
+
+/// Iterator over repository listing which fetches next page only when the current one is consumed
+pub struct ListIter<'c, 'a> {
+    client: &'c mut Client<'a>,
+    user_id: String,
+    repo_name: String,
+    path: String,
+    opts: ListOptions,
+    buffer: VecDeque<ListEntry>,
+    done: bool,
+}
+
+impl Iterator for ListIter<'_, '_> {
+    type Item = Result<ListEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() && !self.done {
+            let page = match self.client.list(
+                &self.user_id,
+                &self.repo_name,
+                &self.path,
+                Some(self.opts.clone()),
+            ) {
+                Ok(page) => page,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            };
+
+            self.opts.offset = None;
+            self.done = page.cursor.is_none() || page.entries.is_empty();
+            self.opts.cursor = page.cursor;
+            self.buffer.extend(page.entries);
+        }
+
+        self.buffer.pop_front().map(Ok)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{
+        AuthError, EncryptionError, HTTPErrorKind, MnemonicError, SignedRecordError,
+    };
+    use crate::state::FileStateStore;
+    use crate::test_utils::*;
+    use crate::transport::crypto::{DeterministicKeyGen, Keypair};
+    use crate::transport::resolver::store::PacketStore;
+    use mainline::dht::Testnet;
+
+    #[test]
+    fn test_client_new() {
+        let testnet = Testnet::new(10);
+        let seed = b"it is a seed for key generation!";
+
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let server = create_homeserver_mock(
+            user_id.to_string(),
+            "repo_name".to_string(),
+            "folder_path".to_string(),
+            "data".to_string(),
+        );
+        let _ = publish_url(
+            &key_pair,
+            &Url::parse(&server.url()).unwrap(),
+            &testnet.bootstrap,
+        );
+
+        let client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap)).unwrap();
+
+        assert_eq!(client.homeservers_cache.len(), 1);
+        assert_eq!(
+            client
+                .homeservers_cache
+                .get(&user_id)
+                .unwrap()
+                .homeserver_url,
+            Some(Url::parse(&server.url()).unwrap())
+        );
+        assert_eq!(
+            client
+                .homeservers_cache
+                .get(&user_id)
+                .unwrap()
+                .session_id(&user_id),
+            Some("send_signature_signup".to_string())
+        );
+    }
+
+    #[test]
+    fn test_client_create() {
+        let seed = b"it is a seed for key generation!";
+        let testnet = Testnet::new(10);
+
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let repo_name = "test_repo";
+
+        let server = create_homeserver_mock(
+            user_id.to_string(),
+            repo_name.to_string(),
+            "folder_path".to_string(),
+            "data".to_string(),
         );
         let _ = publish_url(
             &key_pair,
             &Url::parse(&server.url()).unwrap(),
             &testnet.bootstrap,
         );
-        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap));
+        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap)).unwrap();
 
         let result = client.create(&user_id, repo_name);
 
@@ -344,7 +1051,11 @@ mod tests {
             Some(Url::parse(&server.url()).unwrap())
         );
         assert_eq!(
-            client.homeservers_cache.get(&user_id).unwrap().session_id,
+            client
+                .homeservers_cache
+                .get(&user_id)
+                .unwrap()
+                .session_id(&user_id),
             Some("create_repo".to_string())
         );
     }
@@ -372,7 +1083,7 @@ mod tests {
             &testnet.bootstrap,
         );
 
-        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap));
+        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap)).unwrap();
 
         let result = client.put(&user_id, repo_name, folder_path, "test_payload");
 
@@ -395,7 +1106,11 @@ mod tests {
             Some(Url::parse(&server.url()).unwrap())
         );
         assert_eq!(
-            client.homeservers_cache.get(&user_id).unwrap().session_id,
+            client
+                .homeservers_cache
+                .get(&user_id)
+                .unwrap()
+                .session_id(&user_id),
             Some("create_folder".to_string())
         );
     }
@@ -425,7 +1140,7 @@ mod tests {
             &testnet.bootstrap,
         );
 
-        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap));
+        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap)).unwrap();
 
         let result = client.get(&user_id, repo_name, folder_path);
 
@@ -440,7 +1155,11 @@ mod tests {
             Some(Url::parse(&server.url()).unwrap())
         );
         assert_eq!(
-            client.homeservers_cache.get(&user_id).unwrap().session_id,
+            client
+                .homeservers_cache
+                .get(&user_id)
+                .unwrap()
+                .session_id(&user_id),
             Some("get_data".to_string())
         );
     }
@@ -468,7 +1187,7 @@ mod tests {
             &testnet.bootstrap,
         );
 
-        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap));
+        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap)).unwrap();
 
         let result = client.delete(&user_id, repo_name, folder_path);
 
@@ -483,8 +1202,1154 @@ mod tests {
             Some(Url::parse(&server.url()).unwrap())
         );
         assert_eq!(
-            client.homeservers_cache.get(&user_id).unwrap().session_id,
+            client
+                .homeservers_cache
+                .get(&user_id)
+                .unwrap()
+                .session_id(&user_id),
             Some("delete_data".to_string())
         );
     }
+
+    #[test]
+    fn test_client_list() {
+        let testnet = Testnet::new(10);
+
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let repo_name = "test_repo";
+        let folder_path = "test_path";
+
+        let mut server = create_homeserver_mock(
+            user_id.to_string(),
+            repo_name.to_string(),
+            folder_path.to_string(),
+            "data".to_string(),
+        );
+
+        let path = format!(
+            "/mvp/users/{}/repos/{}/{}/?reverse=true&offset=1&limit=2",
+            user_id, repo_name, folder_path
+        );
+        let body = br#"{"entries":[{"path":"test_path/c","size":3,"updated_at":42},{"path":"test_path/b"}],"cursor":"b"}"#.to_vec();
+        add_mocks(
+            &mut server,
+            vec![HttpMockParams {
+                method: &Method::GET,
+                path: path.as_str(),
+                status: 200,
+                body: &body,
+                headers: vec![],
+            }],
+        );
+
+        let _ = publish_url(
+            &key_pair,
+            &Url::parse(&server.url()).unwrap(),
+            &testnet.bootstrap,
+        );
+
+        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap)).unwrap();
+
+        let result = client
+            .list(
+                &user_id,
+                repo_name,
+                folder_path,
+                Some(ListOptions {
+                    reverse: true,
+                    offset: Some(1),
+                    limit: Some(2),
+                    cursor: None,
+                }),
+            )
+            .unwrap();
+
+        assert_eq!(
+            result,
+            ListPage {
+                entries: vec![
+                    ListEntry {
+                        path: "test_path/c".to_string(),
+                        size: Some(3),
+                        updated_at: Some(42),
+                    },
+                    ListEntry {
+                        path: "test_path/b".to_string(),
+                        size: None,
+                        updated_at: None,
+                    },
+                ],
+                cursor: Some("b".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_client_list_iter() {
+        let testnet = Testnet::new(10);
+
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let repo_name = "test_repo";
+
+        let mut server = create_homeserver_mock(
+            user_id.to_string(),
+            repo_name.to_string(),
+            "folder_path".to_string(),
+            "data".to_string(),
+        );
+
+        let first_path = format!("/mvp/users/{}/repos/{}/?limit=2", user_id, repo_name);
+        let first_body = br#"{"entries":[{"path":"a"},{"path":"b"}],"cursor":"b"}"#.to_vec();
+        let second_path = format!(
+            "/mvp/users/{}/repos/{}/?limit=2&cursor=b",
+            user_id, repo_name
+        );
+        let second_body = br#"{"entries":[{"path":"c"}],"cursor":null}"#.to_vec();
+        add_mocks(
+            &mut server,
+            vec![
+                HttpMockParams {
+                    method: &Method::GET,
+                    path: first_path.as_str(),
+                    status: 200,
+                    body: &first_body,
+                    headers: vec![],
+                },
+                HttpMockParams {
+                    method: &Method::GET,
+                    path: second_path.as_str(),
+                    status: 200,
+                    body: &second_body,
+                    headers: vec![],
+                },
+            ],
+        );
+
+        let _ = publish_url(
+            &key_pair,
+            &Url::parse(&server.url()).unwrap(),
+            &testnet.bootstrap,
+        );
+
+        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap)).unwrap();
+
+        let paths: Vec<String> = client
+            .list_iter(
+                &user_id,
+                repo_name,
+                "/",
+                Some(ListOptions {
+                    limit: Some(2),
+                    ..Default::default()
+                }),
+            )
+            .map(|entry| entry.unwrap().path)
+            .collect();
+
+        assert_eq!(paths, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_client_query() {
+        let testnet = Testnet::new(10);
+
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let repo_name = "test_repo";
+
+        let mut server = create_homeserver_mock(
+            user_id.to_string(),
+            repo_name.to_string(),
+            "folder_path".to_string(),
+            "data".to_string(),
+        );
+
+        let path = format!(
+            "/mvp/users/{}/repos/{}?start=posts%2F001&end=posts%2F100&limit=2&reverse=true",
+            user_id, repo_name
+        );
+        let body = br#"[{"key":"posts/099","value":"latest"},{"key":"posts/098","value":"older"}]"#
+            .to_vec();
+        add_mocks(
+            &mut server,
+            vec![HttpMockParams {
+                method: &Method::GET,
+                path: path.as_str(),
+                status: 200,
+                body: &body,
+                headers: vec![],
+            }],
+        );
+
+        let _ = publish_url(
+            &key_pair,
+            &Url::parse(&server.url()).unwrap(),
+            &testnet.bootstrap,
+        );
+
+        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap)).unwrap();
+
+        let result = client
+            .query(
+                &user_id,
+                repo_name,
+                Some(QueryOptions {
+                    start: Some("posts/001".to_string()),
+                    end: Some("posts/100".to_string()),
+                    limit: Some(2),
+                    reverse: true,
+                }),
+            )
+            .unwrap();
+
+        assert_eq!(
+            result,
+            vec![
+                QueryEntry {
+                    key: "posts/099".to_string(),
+                    value: "latest".to_string(),
+                },
+                QueryEntry {
+                    key: "posts/098".to_string(),
+                    value: "older".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_client_put_failed() {
+        let testnet = Testnet::new(10);
+        let seed = b"it is a seed for key generation!";
+
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let repo_name = "test_repo";
+
+        let mut server = create_homeserver_mock(
+            user_id.to_string(),
+            repo_name.to_string(),
+            "folder_path".to_string(),
+            "data".to_string(),
+        );
+
+        let path = format!("/mvp/users/{}/repos/{}/full", user_id, repo_name);
+        let missing_path = format!("/mvp/users/{}/repos/{}/not_existing", user_id, repo_name);
+        add_mocks(
+            &mut server,
+            vec![
+                HttpMockParams {
+                    method: &Method::PUT,
+                    path: path.as_str(),
+                    status: 507,
+                    body: &b"quota exceeded".to_vec(),
+                    headers: vec![],
+                },
+                HttpMockParams {
+                    method: &Method::GET,
+                    path: missing_path.as_str(),
+                    status: 404,
+                    body: &b"not found".to_vec(),
+                    headers: vec![],
+                },
+            ],
+        );
+
+        let _ = publish_url(
+            &key_pair,
+            &Url::parse(&server.url()).unwrap(),
+            &testnet.bootstrap,
+        );
+
+        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap)).unwrap();
+
+        let err = client
+            .put(&user_id, repo_name, "full", "test_payload")
+            .unwrap_err();
+        assert!(matches!(err, Error::FailedToStoreData(_)));
+        assert_eq!(err.kind(), Some(HTTPErrorKind::QuotaExceeded));
+
+        let err = client.get(&user_id, repo_name, "not_existing").unwrap_err();
+        assert!(matches!(err, Error::FailedToRetrieveData(_)));
+        assert_eq!(err.http_error().unwrap().status(), Some(404));
+        assert_eq!(err.kind(), Some(HTTPErrorKind::NotFound));
+    }
+
+    #[test]
+    fn test_client_put_get_bytes() {
+        let testnet = Testnet::new(10);
+        let seed = b"it is a seed for key generation!";
+
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let repo_name = "test_repo";
+        let folder_path = "image.png";
+        let data = vec![0x89, 0x50, 0x4e, 0x47, 0xff, 0xfe, 0x00, 0x01];
+
+        let mut server = create_homeserver_mock(
+            user_id.to_string(),
+            repo_name.to_string(),
+            "folder_path".to_string(),
+            "data".to_string(),
+        );
+
+        let path = format!("/mvp/users/{}/repos/{}/{}", user_id, repo_name, folder_path);
+        add_mocks(
+            &mut server,
+            vec![
+                HttpMockParams {
+                    method: &Method::PUT,
+                    path: path.as_str(),
+                    status: 200,
+                    body: &b"ok".to_vec(),
+                    headers: vec![],
+                },
+                HttpMockParams {
+                    method: &Method::GET,
+                    path: path.as_str(),
+                    status: 200,
+                    body: &data,
+                    headers: vec![],
+                },
+            ],
+        );
+
+        let _ = publish_url(
+            &key_pair,
+            &Url::parse(&server.url()).unwrap(),
+            &testnet.bootstrap,
+        );
+
+        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap)).unwrap();
+
+        let result = client.put_bytes(&user_id, repo_name, folder_path, &data);
+        assert!(result.is_ok());
+
+        let result = client.get_bytes(&user_id, repo_name, folder_path);
+        assert_eq!(result.unwrap(), data);
+
+        let result = client.get(&user_id, repo_name, folder_path);
+        assert!(matches!(result, Err(Error::InvalidUtf8(_))));
+    }
+
+    #[test]
+    fn test_client_put_get_encrypted() {
+        let seed = b"it is a seed for key generation!";
+        let user_id = Keypair::generate(Some(seed)).to_z32();
+        let repo_name = "test_repo";
+        let data = b"secret payload";
+
+        let mut server = mockito::Server::new();
+        let record = add_record_mock(
+            &mut server,
+            &format!("/mvp/users/{}/repos/{}/secret", user_id, repo_name),
+        );
+        let moved = add_record_mock(
+            &mut server,
+            &format!("/mvp/users/{}/repos/{}/moved", user_id, repo_name),
+        );
+
+        let mut client = Client::builder()
+            .seed(*seed)
+            .homeserver_url(Url::parse(&server.url()).unwrap())
+            .build()
+            .unwrap();
+
+        client
+            .put_encrypted(&user_id, repo_name, "secret", data)
+            .unwrap();
+        let stored = record.lock().unwrap().clone();
+        assert!(!stored.windows(data.len()).any(|w| w == data));
+
+        assert_eq!(
+            client.get_decrypted(&user_id, repo_name, "secret").unwrap(),
+            data
+        );
+
+        // Ciphertext copied to another path is rejected
+        *moved.lock().unwrap() = stored.clone();
+        assert!(matches!(
+            client.get_decrypted(&user_id, repo_name, "moved"),
+            Err(Error::FailedToDecryptData(EncryptionError::FailedToDecrypt))
+        ));
+
+        // Tampered ciphertext is rejected
+        record.lock().unwrap()[30] ^= 1;
+        assert!(matches!(
+            client.get_decrypted(&user_id, repo_name, "secret"),
+            Err(Error::FailedToDecryptData(EncryptionError::FailedToDecrypt))
+        ));
+    }
+
+    #[test]
+    fn test_client_put_get_shared() {
+        let seed = b"it is a seed for key generation!";
+        let user_id = Keypair::generate(Some(seed)).to_z32();
+        let friend_seed = b"it is another seed for key gen!!";
+        let friend_id = Keypair::generate(Some(friend_seed)).to_z32();
+        let repo_name = "test_repo";
+
+        let mut server = mockito::Server::new();
+        add_record_mock(
+            &mut server,
+            &format!("/mvp/users/{}/repos/{}/shared", user_id, repo_name),
+        );
+        let url = Url::parse(&server.url()).unwrap();
+
+        let mut client = Client::builder()
+            .seed(*seed)
+            .homeserver_url(url.clone())
+            .build()
+            .unwrap();
+        client
+            .put_shared(&user_id, repo_name, "shared", b"for friends", &[&friend_id])
+            .unwrap();
+        assert_eq!(
+            client.get_shared(&user_id, repo_name, "shared").unwrap(),
+            b"for friends"
+        );
+
+        // The friend reads it from the owner's homeserver with their own seed
+        let mut friend = Client::builder()
+            .seed(*friend_seed)
+            .state({
+                let mut state = ClientState::new(friend_id.clone());
+                state.homeservers.insert(user_id.clone(), url.to_string());
+                state
+            })
+            .build()
+            .unwrap();
+        assert_eq!(
+            friend.get_shared(&user_id, repo_name, "shared").unwrap(),
+            b"for friends"
+        );
+
+        let stranger_seed = b"it is a stranger seed for keygen";
+        let mut stranger = Client::builder()
+            .seed(*stranger_seed)
+            .state({
+                let mut state = ClientState::new(Keypair::generate(Some(stranger_seed)).to_z32());
+                state.homeservers.insert(user_id.clone(), url.to_string());
+                state
+            })
+            .build()
+            .unwrap();
+        assert!(matches!(
+            stranger.get_shared(&user_id, repo_name, "shared"),
+            Err(Error::FailedToDecryptData(EncryptionError::NotARecipient))
+        ));
+
+        let err = client
+            .put_shared(&user_id, repo_name, "shared", b"data", &["invalid"])
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidUserId(_)));
+    }
+
+    #[test]
+    fn test_client_put_get_verified() {
+        let seed = b"it is a seed for key generation!";
+        let user_id = Keypair::generate(Some(seed)).to_z32();
+        let repo_name = "test_repo";
+
+        let mut server = mockito::Server::new();
+        let record = add_record_mock(
+            &mut server,
+            &format!("/mvp/users/{}/repos/{}/post", user_id, repo_name),
+        );
+        let forged = add_record_mock(
+            &mut server,
+            &format!("/mvp/users/{}/repos/{}/forged", user_id, repo_name),
+        );
+
+        let mut client = Client::builder()
+            .seed(*seed)
+            .homeserver_url(Url::parse(&server.url()).unwrap())
+            .build()
+            .unwrap();
+
+        client
+            .put_signed(&user_id, repo_name, "post", b"hello")
+            .unwrap();
+        let verified = client.get_verified(&user_id, repo_name, "post").unwrap();
+        assert_eq!(verified.payload, b"hello");
+        assert_eq!(verified.path, "test_repo/post");
+
+        // Records are only signed into the own repositories
+        let other_user_id = Keypair::random().to_z32();
+        assert!(matches!(
+            client.put_signed(&other_user_id, repo_name, "post", b"hello"),
+            Err(Error::FailedToSignData(SignedRecordError::NotAuthor(_)))
+        ));
+
+        // Homeserver serving a record signed for another path
+        *forged.lock().unwrap() = record.lock().unwrap().clone();
+        assert!(matches!(
+            client.get_verified(&user_id, repo_name, "forged"),
+            Err(Error::FailedToVerifyData(
+                SignedRecordError::PathMismatch { .. }
+            ))
+        ));
+
+        // Homeserver forging a record with its own key
+        let homeserver_keypair = Keypair::random();
+        *forged.lock().unwrap() =
+            SignedRecord::sign(&homeserver_keypair, "test_repo/forged", b"forged")
+                .unwrap()
+                .to_bytes()
+                .unwrap();
+        assert!(matches!(
+            client.get_verified(&user_id, repo_name, "forged"),
+            Err(Error::FailedToVerifyData(
+                SignedRecordError::InvalidSignature
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_client_moved_homeserver() {
+        let testnet = Testnet::new(10);
+
+        let friend = Keypair::generate(Some(b"it is another seed for key gen!!"));
+        let friend_id = friend.to_z32();
+        let path = format!("/mvp/users/{}/repos/test_repo/test_path", friend_id);
+        let servers: Vec<_> = [b"old".to_vec(), b"new".to_vec()]
+            .iter()
+            .map(|body| {
+                create_server(vec![HttpMockParams {
+                    method: &Method::GET,
+                    path: &path,
+                    status: 200,
+                    body,
+                    headers: vec![],
+                }])
+            })
+            .collect();
+        let old_url = Url::parse(&servers[0].url()).unwrap();
+        let new_url = Url::parse(&servers[1].url()).unwrap();
+
+        let mut client = Client::builder()
+            .seed(*b"it is a seed for key generation!")
+            .homeserver_url(Url::parse("http://localhost:1").unwrap())
+            .bootstrap(&testnet.bootstrap)
+            .build()
+            .unwrap();
+
+        let _ = publish_url(&friend, &old_url, &testnet.bootstrap);
+        assert_eq!(
+            client.get(&friend_id, "test_repo", "test_path").unwrap(),
+            "old"
+        );
+
+        // The cached homeserver is used until it expires or is invalidated
+        let _ = publish_url(&friend, &new_url, &testnet.bootstrap);
+        assert_eq!(
+            client.get(&friend_id, "test_repo", "test_path").unwrap(),
+            "old"
+        );
+
+        client.invalidate(&friend_id).unwrap();
+        assert_eq!(
+            client.get(&friend_id, "test_repo", "test_path").unwrap(),
+            "new"
+        );
+        assert_eq!(
+            client
+                .homeservers_cache
+                .get(&friend_id)
+                .unwrap()
+                .homeserver_url,
+            Some(new_url)
+        );
+
+        // Expired record is looked up again by the known user as well
+        let _ = publish_url(&friend, &old_url, &testnet.bootstrap);
+        client.resolver.invalidate(&friend.public_key());
+        assert_eq!(
+            client.get(&friend_id, "test_repo", "test_path").unwrap(),
+            "old"
+        );
+
+        assert!(matches!(
+            client.invalidate("invalid"),
+            Err(Error::InvalidUserId(_))
+        ));
+    }
+
+    #[test]
+    fn test_client_builder_cache_strategy() {
+        let testnet = Testnet::new(10);
+
+        let friend = Keypair::generate(Some(b"it is another seed for key gen!!"));
+        let friend_id = friend.to_z32();
+        let path = format!("/mvp/users/{}/repos/test_repo/test_path", friend_id);
+        let servers: Vec<_> = [b"old".to_vec(), b"new".to_vec()]
+            .iter()
+            .map(|body| {
+                create_server(vec![HttpMockParams {
+                    method: &Method::GET,
+                    path: &path,
+                    status: 200,
+                    body,
+                    headers: vec![],
+                }])
+            })
+            .collect();
+        let old_url = Url::parse(&servers[0].url()).unwrap();
+        let new_url = Url::parse(&servers[1].url()).unwrap();
+
+        let mut client = Client::builder()
+            .seed(*b"it is a seed for key generation!")
+            .homeserver_url(Url::parse("http://localhost:1").unwrap())
+            .bootstrap(&testnet.bootstrap)
+            .cache_strategy(CacheStrategy::StaleWhileRevalidate {
+                max_stale: Duration::from_secs(60),
+            })
+            .build()
+            .unwrap();
+
+        let _ = publish_url(&friend, &new_url, &testnet.bootstrap);
+        client
+            .resolver
+            .cache()
+            .insert(&friend.public_key(), old_url, Duration::ZERO);
+
+        // Expired homeserver is still served while it is refreshed
+        assert_eq!(
+            client.get(&friend_id, "test_repo", "test_path").unwrap(),
+            "old"
+        );
+        for _ in 0..100 {
+            if client.resolver.cache().get(&friend.public_key()).is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert_eq!(
+            client.get(&friend_id, "test_repo", "test_path").unwrap(),
+            "new"
+        );
+    }
+
+    #[test]
+    fn test_client_builder_resolver_cache() {
+        let testnet = Testnet::new(10);
+        let other_testnet = Testnet::new(3);
+        let dir = TempDir::new("pdk-packets");
+
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let server = create_homeserver_mock(
+            user_id.to_string(),
+            "test_repo".to_string(),
+            "test_path".to_string(),
+            "test_payload".to_string(),
+        );
+        let url = Url::parse(&server.url()).unwrap();
+        let _ = publish_url(&key_pair, &url, &testnet.bootstrap);
+
+        let cache = ResolverCache::new().with_packet_store(PacketStore::open(dir.path()).unwrap());
+        let mut client = Client::builder()
+            .seed(*seed)
+            .bootstrap(&testnet.bootstrap)
+            .resolver_cache(cache)
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.get(&user_id, "test_repo", "test_path").unwrap(),
+            "test_payload"
+        );
+
+        // Restarted client resolves from disk, the packet is not published on its DHT
+        let cache = ResolverCache::new().with_packet_store(PacketStore::open(dir.path()).unwrap());
+        let mut client = Client::builder()
+            .seed(*seed)
+            .bootstrap(&other_testnet.bootstrap)
+            .resolver_cache(cache)
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.get(&user_id, "test_repo", "test_path").unwrap(),
+            "test_payload"
+        );
+        assert_eq!(client.homeserver_url, Some(url));
+    }
+
+    #[test]
+    fn test_client_builder_offline() {
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let homeserver_url = Url::parse("http://localhost:1").unwrap();
+
+        let client = Client::builder()
+            .seed(*seed)
+            .homeserver_url(homeserver_url.clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(client.user_id, key_pair.to_z32());
+        assert_eq!(client.homeserver_url, Some(homeserver_url));
+        assert_eq!(
+            client
+                .homeservers_cache
+                .get(&client.user_id)
+                .unwrap()
+                .session_id(&client.user_id),
+            None
+        );
+
+        let client = Client::builder().keypair(&key_pair).build().unwrap();
+        assert_eq!(client.user_id, key_pair.to_z32());
+        assert_eq!(client.homeserver_url, None);
+    }
+
+    #[test]
+    fn test_client_builder_unreachable_homeserver() {
+        let seed = b"it is a seed for key generation!";
+
+        let result = Client::builder()
+            .seed(*seed)
+            .homeserver_url(Url::parse("http://localhost:1").unwrap())
+            .timeout(Duration::from_secs(1))
+            .signup()
+            .build();
+
+        assert!(matches!(result, Err(Error::FailedToSignup(_))));
+    }
+
+    #[test]
+    fn test_client_builder_login() {
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+
+        let server = create_homeserver_mock(
+            user_id.to_string(),
+            "repo_name".to_string(),
+            "folder_path".to_string(),
+            "data".to_string(),
+        );
+
+        let client = Client::builder()
+            .seed(*seed)
+            .homeserver_url(Url::parse(&server.url()).unwrap())
+            .login()
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            client
+                .homeservers_cache
+                .get(&user_id)
+                .unwrap()
+                .session_id(&user_id),
+            Some("send_signature_login".to_string())
+        );
+    }
+
+    #[test]
+    fn test_client_session_cookie_sent() {
+        let testnet = Testnet::new(10);
+        let seed = b"it is a seed for key generation!";
+        let user_id = Keypair::generate(Some(seed)).to_z32();
+
+        let mut server = mockito::Server::new();
+        add_challenge_mock(&mut server);
+        server
+            .mock("PUT", format!("/mvp/users/{}/pkarr", user_id).as_str())
+            .with_header("Set-Cookie", "sessionId=signup_session")
+            .create();
+        server
+            .mock("PUT", format!("/mvp/session/{}", user_id).as_str())
+            .with_header("Set-Cookie", "sessionId=login_session")
+            .create();
+        // Only answered when the cookie set at signup or login is sent along
+        server
+            .mock("GET", "/mvp/session")
+            .match_header("cookie", "sessionId=signup_session")
+            .with_body(format!(
+                r#"{{"users":{{"{}":{{"permissions":[]}}}}}}"#,
+                user_id
+            ))
+            .create();
+        server
+            .mock(
+                "PUT",
+                format!("/mvp/users/{}/repos/repo/path", user_id).as_str(),
+            )
+            .match_header("cookie", "sessionId=login_session")
+            .create();
+
+        let mut client = Client::builder()
+            .seed(*seed)
+            .homeserver_url(Url::parse(&server.url()).unwrap())
+            .bootstrap(&testnet.bootstrap)
+            .signup()
+            .build()
+            .unwrap();
+        assert!(client.session().unwrap().is_some());
+
+        client.login().unwrap();
+        client.put(&user_id, "repo", "path", "data").unwrap();
+    }
+
+    #[test]
+    fn test_client_sessions() {
+        let seed = b"it is a seed for key generation!";
+        let user_id = Keypair::generate(Some(seed)).to_z32();
+        let other_seed = b"it is another seed for key gen!!";
+        let other_user_id = Keypair::generate(Some(other_seed)).to_z32();
+
+        let mut server = create_homeserver_mock(
+            user_id.to_string(),
+            "repo_name".to_string(),
+            "folder_path".to_string(),
+            "data".to_string(),
+        );
+        let path = format!("/mvp/session/{}", other_user_id);
+        add_mocks(
+            &mut server,
+            vec![
+                HttpMockParams {
+                    method: &Method::PUT,
+                    path: path.as_str(),
+                    status: 200,
+                    body: &b"ok".to_vec(),
+                    headers: vec![("Set-Cookie", "sessionId=other_login; Path=/mvp")],
+                },
+                HttpMockParams {
+                    method: &Method::DELETE,
+                    path: path.as_str(),
+                    status: 200,
+                    body: &b"ok".to_vec(),
+                    headers: vec![],
+                },
+            ],
+        );
+        let url = Url::parse(&server.url()).unwrap();
+
+        // Two accounts at the same homeserver, sharing one session store
+        let sessions = SessionStore::new();
+        let mut client = Client::builder()
+            .seed(*seed)
+            .homeserver_url(url.clone())
+            .sessions(sessions.clone())
+            .login()
+            .build()
+            .unwrap();
+        let other_client = Client::builder()
+            .seed(*other_seed)
+            .homeserver_url(url.clone())
+            .sessions(sessions.clone())
+            .login()
+            .build()
+            .unwrap();
+
+        let mut listed = client.sessions();
+        listed.sort_by(|a, b| a.value.cmp(&b.value));
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].value, "other_login");
+        assert_eq!(listed[0].user_id, other_user_id);
+        assert_eq!(listed[0].path, "/mvp");
+        assert_eq!(listed[1].value, "send_signature_login");
+        assert_eq!(listed[1].user_id, user_id);
+
+        assert_eq!(
+            client.revoke_session(&url, &other_user_id).unwrap(),
+            "other_login"
+        );
+        assert_eq!(other_client.sessions().len(), 1);
+        assert_eq!(client.sessions()[0].user_id, user_id);
+
+        let err = client.revoke_session(&url, &other_user_id).unwrap_err();
+        assert!(matches!(err, Error::FailedToLogout(AuthError::NoSession)));
+    }
+
+    #[test]
+    fn test_client_restore_state() {
+        let seed = b"it is a seed for key generation!";
+        let user_id = Keypair::generate(Some(seed)).to_z32();
+
+        let server = create_homeserver_mock(
+            user_id.to_string(),
+            "repo_name".to_string(),
+            "folder_path".to_string(),
+            "data".to_string(),
+        );
+        let url = Url::parse(&server.url()).unwrap();
+        let dir = TempDir::new("pdk-client-state");
+        let path = dir.join("state.json");
+        let store = FileStateStore::new(&path);
+
+        let client = Client::builder()
+            .seed(*seed)
+            .homeserver_url(url.clone())
+            .login()
+            .build()
+            .unwrap();
+        client.save_state(&store).unwrap();
+
+        // No signup or login, the homeserver and session come from the saved state
+        let mut client = Client::builder()
+            .seed(*seed)
+            .state(store.load().unwrap().unwrap())
+            .build()
+            .unwrap();
+
+        assert_eq!(client.homeserver_url, Some(url.clone()));
+        assert_eq!(client.sessions()[0].value, "send_signature_login");
+        assert!(client.session().unwrap().is_some());
+        assert_eq!(
+            client.get(&user_id, "repo_name", "folder_path").unwrap(),
+            "data"
+        );
+
+        // State of another user is rejected
+        let other_seed = b"it is another seed for key gen!!";
+        let err = Client::builder()
+            .seed(*other_seed)
+            .state(client.state())
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            Error::FailedToRestoreState(StateError::InvalidState(_))
+        ));
+
+        // Session revoked while the client was not running is dropped on first check
+        let expired_server = create_server(vec![HttpMockParams {
+            method: &Method::GET,
+            path: "/mvp/session",
+            status: 401,
+            body: &b"unauthorized".to_vec(),
+            headers: vec![],
+        }]);
+        let expired_url = Url::parse(&expired_server.url()).unwrap();
+        let mut state = ClientState::new(user_id.clone());
+        state
+            .homeservers
+            .insert(user_id.clone(), expired_url.to_string());
+        state.sessions.push(SessionCookie {
+            homeserver: expired_url.origin().ascii_serialization(),
+            user_id: user_id.clone(),
+            value: "revoked".to_string(),
+            path: "/".to_string(),
+            secure: false,
+            expires_at: None,
+        });
+
+        let mut client = Client::builder().seed(*seed).state(state).build().unwrap();
+        assert_eq!(client.sessions().len(), 1);
+        assert_eq!(
+            client.session().unwrap_err().kind(),
+            Some(HTTPErrorKind::Unauthorized)
+        );
+        assert!(client.sessions().is_empty());
+    }
+
+    #[test]
+    fn test_client_restore_state_moved_homeserver() {
+        let testnet = Testnet::new(10);
+        let seed = b"it is a seed for key generation!";
+        let key_pair = Keypair::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let friend = Keypair::generate(Some(b"it is another seed for key gen!!"));
+        let friend_id = friend.to_z32();
+
+        let mut mocks = vec![];
+        let servers: Vec<_> = ["old", "new"]
+            .iter()
+            .map(|body| {
+                let mut server = mockito::Server::new();
+                for id in [&user_id, &friend_id] {
+                    mocks.push(
+                        server
+                            .mock("GET", format!("/mvp/users/{}/repos/repo/path", id).as_str())
+                            .with_body(body)
+                            .create(),
+                    );
+                }
+                server
+            })
+            .collect();
+        let old_url = Url::parse(&servers[0].url()).unwrap();
+        let new_url = Url::parse(&servers[1].url()).unwrap();
+
+        // Both users moved to the new homeserver after the state was saved
+        let _ = publish_url(&key_pair, &new_url, &testnet.bootstrap);
+        let _ = publish_url(&friend, &new_url, &testnet.bootstrap);
+        let mut state = ClientState::new(user_id.clone());
+        state
+            .homeservers
+            .insert(user_id.clone(), old_url.to_string());
+        state.expires_at.insert(user_id.clone(), now() - 1);
+        state
+            .homeservers
+            .insert(friend_id.clone(), old_url.to_string());
+        state.expires_at.insert(friend_id.clone(), now() + 60);
+
+        let mut client = Client::builder()
+            .seed(*seed)
+            .bootstrap(&testnet.bootstrap)
+            .state(state)
+            .build()
+            .unwrap();
+        assert_eq!(client.homeserver_url, Some(old_url.clone()));
+
+        // Expired homeserver is resolved again, a live one is used until invalidated
+        assert_eq!(client.get(&user_id, "repo", "path").unwrap(), "new");
+        assert_eq!(client.homeserver_url, Some(new_url.clone()));
+        assert_eq!(client.get(&friend_id, "repo", "path").unwrap(), "old");
+        client.invalidate(&friend_id).unwrap();
+        assert_eq!(client.get(&friend_id, "repo", "path").unwrap(), "new");
+
+        let state = client.state();
+        assert_eq!(state.homeservers[&friend_id], new_url.to_string());
+        assert!(state.expires_at[&friend_id] > now());
+    }
+
+    #[test]
+    fn test_client_builder_mnemonic() {
+        let seed = b"it is a seed for key generation!";
+        let client = Client::builder().seed(*seed).build().unwrap();
+
+        let restored = Client::builder()
+            .mnemonic(&client.mnemonic())
+            .build()
+            .unwrap();
+        assert_eq!(restored.user_id, client.user_id);
+
+        let err = Client::builder()
+            .mnemonic("not a recovery phrase")
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            Error::InvalidMnemonic(MnemonicError::InvalidWordCount(4))
+        ));
+    }
+
+    #[test]
+    fn test_client_derive_keypair() {
+        let seed = b"it is a seed for key generation!";
+        let client = Client::builder().seed(*seed).build().unwrap();
+
+        let keypair = client.derive_keypair("app:chat/v1");
+        assert_ne!(keypair.to_z32(), client.user_id);
+
+        let app = Client::builder()
+            .seed(client.derive_seed("app:chat/v1"))
+            .build()
+            .unwrap();
+        assert_eq!(app.user_id, keypair.to_z32());
+    }
+
+    #[test]
+    fn test_client_builder_lazy_homeserver() {
+        let testnet = Testnet::new(10);
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let repo_name = "test_repo";
+
+        let server = create_homeserver_mock(
+            user_id.to_string(),
+            repo_name.to_string(),
+            "folder_path".to_string(),
+            "data".to_string(),
+        );
+        let _ = publish_url(
+            &key_pair,
+            &Url::parse(&server.url()).unwrap(),
+            &testnet.bootstrap,
+        );
+
+        let mut client = Client::builder()
+            .seed(*seed)
+            .bootstrap(&testnet.bootstrap)
+            .build()
+            .unwrap();
+        assert_eq!(client.homeserver_url, None);
+
+        client.create(&user_id, repo_name).unwrap();
+        assert_eq!(
+            client.homeserver_url,
+            Some(Url::parse(&server.url()).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_client_get_other_user() {
+        let testnet = Testnet::new(10);
+
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+
+        let other_seed = b"it is a seed of the other user!!";
+        let other_key_pair: Keypair = DeterministicKeyGen::generate(Some(other_seed));
+        let other_user_id = other_key_pair.to_z32();
+
+        let repo_name = "test_repo";
+        let folder_path = "test_path";
+
+        let server = create_homeserver_mock(
+            user_id.to_string(),
+            repo_name.to_string(),
+            folder_path.to_string(),
+            "data".to_string(),
+        );
+        let other_server = create_homeserver_mock(
+            other_user_id.to_string(),
+            repo_name.to_string(),
+            folder_path.to_string(),
+            "other data".to_string(),
+        );
+
+        let _ = publish_url(
+            &key_pair,
+            &Url::parse(&server.url()).unwrap(),
+            &testnet.bootstrap,
+        );
+        let _ = publish_url(
+            &other_key_pair,
+            &Url::parse(&other_server.url()).unwrap(),
+            &testnet.bootstrap,
+        );
+
+        let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap)).unwrap();
+
+        let result = client.get(&other_user_id, repo_name, folder_path);
+
+        assert_eq!(result.unwrap(), "other data");
+        assert_eq!(client.homeservers_cache.len(), 2);
+        assert_eq!(
+            client
+                .homeservers_cache
+                .get(&other_user_id)
+                .unwrap()
+                .homeserver_url,
+            Some(Url::parse(&other_server.url()).unwrap())
+        );
+        assert_eq!(
+            client.homeserver_url,
+            Some(Url::parse(&server.url()).unwrap())
+        );
+
+        let result = client.get("not a user id", repo_name, folder_path);
+        assert!(matches!(result, Err(Error::InvalidUserId(_))));
+
+        let unknown_user_id = Keypair::random().to_z32();
+        let result = client.list(&unknown_user_id, repo_name, folder_path, None);
+        assert!(matches!(result, Err(Error::FailedToResolveHomeserver(_))));
+        assert_eq!(client.homeservers_cache.len(), 2);
+        assert!(!client.state().homeservers.contains_key(&unknown_user_id));
+    }
 }
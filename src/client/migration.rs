@@ -0,0 +1,471 @@
+use super::{list_url, Client, ListOptions, ListPage};
+use crate::error::{ClientError as Error, MigrationError};
+use crate::transport::{
+    auth::Auth,
+    http::{Bytes, HeaderMap, Method, Url},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Stage of a migration, stages run in the order they are declared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MigrationStage {
+    /// Create the account at the new homeserver
+    Registering,
+    /// Copy every record of the repositories
+    Copying,
+    /// Compare the copies with the originals
+    Verifying,
+    /// Point the `_pubky` record of the user to the new homeserver
+    Publishing,
+    /// Delete the records from the old homeserver, only if `delete_old` is set
+    Deleting,
+    Done,
+}
+
+/// Progress of moving user's repositories from one homeserver to another.
+///
+/// It is updated by [`Client::migrate`] as records are processed and can be saved with
+/// [`Migration::to_bytes`], so an interrupted migration resumes where it stopped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Migration {
+    pub user_id: String,
+    pub from: String,
+    pub to: String,
+    /// Repositories to move, homeservers don't list the repositories of a user
+    pub repos: Vec<String>,
+    pub delete_old: bool,
+    pub stage: MigrationStage,
+    /// Records already copied to the new homeserver, as `(repo, path)`
+    pub copied: BTreeSet<(String, String)>,
+    /// Records already deleted from the old homeserver, as `(repo, path)`
+    pub deleted: BTreeSet<(String, String)>,
+}
+
+impl Migration {
+    pub fn new(user_id: &str, from: &Url, to: &Url, repos: &[&str], delete_old: bool) -> Self {
+        Migration {
+            user_id: user_id.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            repos: repos.iter().map(|repo| repo.to_string()).collect(),
+            delete_old,
+            stage: MigrationStage::Registering,
+            copied: BTreeSet::new(),
+            deleted: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.stage == MigrationStage::Done
+    }
+
+    /// Serializes the migration as JSON
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("Failed to serialize migration")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Migration, MigrationError> {
+        serde_json::from_slice(bytes).map_err(|e| MigrationError::InvalidMigration(e.to_string()))
+    }
+}
+
+impl Client<'_> {
+    /// Start moving the repositories of the user from its current homeserver to another one
+    pub fn migration(
+        &mut self,
+        homeserver_url: &Url,
+        repos: &[&str],
+        delete_old: bool,
+    ) -> Result<Migration, Error> {
+        let user_id = self.user_id.clone();
+        let from = self.homeserver(&user_id)?;
+
+        Ok(Migration::new(
+            &user_id,
+            &from,
+            homeserver_url,
+            repos,
+            delete_old,
+        ))
+    }
+
+    /// Run the migration until it's done.
+    ///
+    /// On failure `migration` keeps the progress made so far, running it again resumes it.
+    pub fn migrate(&mut self, migration: &mut Migration) -> Result<(), Error> {
+        self.run_migration(migration)
+            .map_err(Error::FailedToMigrate)
+    }
+
+    fn run_migration(&mut self, migration: &mut Migration) -> Result<(), MigrationError> {
+        if migration.user_id != self.user_id {
+            return Err(MigrationError::InvalidMigration(format!(
+                "migration belongs to another user {}",
+                migration.user_id
+            )));
+        }
+        let from = Url::parse(&migration.from)
+            .map_err(|e| MigrationError::InvalidMigration(e.to_string()))?;
+        let to = Url::parse(&migration.to)
+            .map_err(|e| MigrationError::InvalidMigration(e.to_string()))?;
+        if from == to {
+            return Err(MigrationError::InvalidMigration(
+                "old and new homeserver are the same".to_string(),
+            ));
+        }
+
+        loop {
+            migration.stage = match migration.stage {
+                MigrationStage::Registering => {
+                    self.register_at(&to)?;
+                    MigrationStage::Copying
+                }
+                MigrationStage::Copying => {
+                    for repo in migration.repos.clone() {
+                        self.copy_repo(migration, &repo, &from, &to)?;
+                    }
+                    MigrationStage::Verifying
+                }
+                MigrationStage::Verifying => {
+                    self.verify_copy(migration, &from, &to)?;
+                    MigrationStage::Publishing
+                }
+                MigrationStage::Publishing => {
+                    self.publish_homeserver(&to)?;
+                    if migration.delete_old {
+                        MigrationStage::Deleting
+                    } else {
+                        MigrationStage::Done
+                    }
+                }
+                MigrationStage::Deleting => {
+                    self.delete_old(migration, &from)?;
+                    MigrationStage::Done
+                }
+                MigrationStage::Done => return Ok(()),
+            };
+        }
+    }
+
+    fn register_at(&mut self, homeserver_url: &Url) -> Result<(), MigrationError> {
+        let mut auth = Auth::new(
+            self.resolver.clone(),
+            self.http.clone(),
+            Some(homeserver_url.clone()),
+        );
+        auth.challenge_policy = self.challenge_policy;
+
+        auth.register(&self.seed, self.dht_relay)
+            .map_err(MigrationError::FailedToRegister)?;
+
+        Ok(())
+    }
+
+    fn copy_repo(
+        &mut self,
+        migration: &mut Migration,
+        repo_name: &str,
+        from: &Url,
+        to: &Url,
+    ) -> Result<(), MigrationError> {
+        let copy_error = |path: &str| {
+            let path = path.to_string();
+            move |error| MigrationError::FailedToCopy { path, error }
+        };
+
+        let url = to
+            .join(&format!("/mvp/users/{}/repos/{}", self.user_id, repo_name))
+            .unwrap();
+        self.http
+            .request(Method::PUT, url, Some(&self.user_id), None, None)
+            .map_err(copy_error(repo_name))?;
+
+        let mut opts = ListOptions::default();
+        loop {
+            let url = list_url(from, &self.user_id, repo_name, "", &opts);
+            let body = self
+                .http
+                .request(Method::GET, url, Some(&self.user_id), None, None)
+                .map_err(copy_error(repo_name))?;
+            let page: ListPage = serde_json::from_slice(&body)
+                .map_err(|e| MigrationError::InvalidResponse(e.to_string()))?;
+
+            for entry in &page.entries {
+                let record = (repo_name.to_string(), entry.path.clone());
+                if migration.copied.contains(&record) {
+                    continue;
+                }
+
+                let path = format!("{}/{}", repo_name, entry.path);
+                let payload = self
+                    .get_record(from, repo_name, &entry.path)
+                    .map_err(copy_error(&path))?;
+
+                let mut headers = HeaderMap::new();
+                headers.insert(
+                    "Content-Type",
+                    "application/octet-stream".try_into().unwrap(),
+                );
+                headers.insert(
+                    "Content-Length",
+                    payload.len().to_string().try_into().unwrap(),
+                );
+                self.http
+                    .request(
+                        Method::PUT,
+                        record_url(to, &self.user_id, repo_name, &entry.path),
+                        Some(&self.user_id),
+                        Some(&headers),
+                        Some(payload.to_vec()),
+                    )
+                    .map_err(copy_error(&path))?;
+
+                migration.copied.insert(record);
+            }
+
+            match page.cursor {
+                Some(cursor) if !page.entries.is_empty() => opts.cursor = Some(cursor),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn verify_copy(
+        &mut self,
+        migration: &Migration,
+        from: &Url,
+        to: &Url,
+    ) -> Result<(), MigrationError> {
+        for (repo_name, path) in &migration.copied {
+            let verify_error = |error| MigrationError::FailedToVerify {
+                path: format!("{}/{}", repo_name, path),
+                error,
+            };
+
+            let original = self
+                .get_record(from, repo_name, path)
+                .map_err(verify_error)?;
+            let copy = self.get_record(to, repo_name, path).map_err(verify_error)?;
+
+            if original != copy {
+                return Err(MigrationError::CopyMismatch(format!(
+                    "{}/{}",
+                    repo_name, path
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn publish_homeserver(&mut self, homeserver_url: &Url) -> Result<(), MigrationError> {
+        let auth = self.homeservers_cache.get_mut(&self.user_id).unwrap();
+        auth.homeserver_url = Some(homeserver_url.clone());
+        auth.publish(&self.seed, self.dht_relay)
+            .map_err(MigrationError::FailedToPublish)?;
+
+        self.homeserver_url = Some(homeserver_url.clone());
+
+        Ok(())
+    }
+
+    fn delete_old(&mut self, migration: &mut Migration, from: &Url) -> Result<(), MigrationError> {
+        for record in migration.copied.clone() {
+            if migration.deleted.contains(&record) {
+                continue;
+            }
+
+            let (repo_name, path) = &record;
+            self.http
+                .request(
+                    Method::DELETE,
+                    record_url(from, &self.user_id, repo_name, path),
+                    Some(&self.user_id),
+                    None,
+                    None,
+                )
+                .map_err(|error| MigrationError::FailedToDeleteOld {
+                    path: format!("{}/{}", repo_name, path),
+                    error,
+                })?;
+
+            migration.deleted.insert(record);
+        }
+
+        Ok(())
+    }
+
+    fn get_record(
+        &self,
+        homeserver_url: &Url,
+        repo_name: &str,
+        path: &str,
+    ) -> Result<Bytes, crate::error::HTTPError> {
+        self.http.request(
+            Method::GET,
+            record_url(homeserver_url, &self.user_id, repo_name, path),
+            Some(&self.user_id),
+            None,
+            None,
+        )
+    }
+}
+
+fn record_url(homeserver_url: &Url, user_id: &str, repo_name: &str, path: &str) -> Url {
+    homeserver_url
+        .join(&format!(
+            "/mvp/users/{}/repos/{}/{}",
+            user_id, repo_name, path
+        ))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::HTTPErrorKind;
+    use crate::test_utils::*;
+    use crate::transport::crypto::{DeterministicKeyGen, Keypair};
+    use crate::transport::resolver::Resolver;
+    use mainline::dht::Testnet;
+
+    #[test]
+    fn test_client_migrate() {
+        let testnet = Testnet::new(10);
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+
+        let mut old_server = create_homeserver_mock(
+            user_id.to_string(),
+            "repo".to_string(),
+            "a".to_string(),
+            "first".to_string(),
+        );
+        let list_path = format!("/mvp/users/{}/repos/repo/", user_id);
+        let list_next_path = format!("{}?cursor=a", list_path);
+        let b_path = format!("/mvp/users/{}/repos/repo/b", user_id);
+        add_mocks(
+            &mut old_server,
+            vec![
+                HttpMockParams {
+                    method: &Method::GET,
+                    path: list_path.as_str(),
+                    status: 200,
+                    body: &br#"{"entries":[{"path":"a"}],"cursor":"a"}"#.to_vec(),
+                    headers: vec![],
+                },
+                HttpMockParams {
+                    method: &Method::GET,
+                    path: list_next_path.as_str(),
+                    status: 200,
+                    body: &br#"{"entries":[{"path":"b"}]}"#.to_vec(),
+                    headers: vec![],
+                },
+                HttpMockParams {
+                    method: &Method::GET,
+                    path: b_path.as_str(),
+                    status: 200,
+                    body: &b"second".to_vec(),
+                    headers: vec![],
+                },
+                HttpMockParams {
+                    method: &Method::DELETE,
+                    path: b_path.as_str(),
+                    status: 200,
+                    body: &b"ok".to_vec(),
+                    headers: vec![],
+                },
+            ],
+        );
+        let old_url = Url::parse(&old_server.url()).unwrap();
+        let _ = publish_url(&key_pair, &old_url, &testnet.bootstrap);
+
+        let pkarr_path = format!("/mvp/users/{}/pkarr", user_id);
+        let repo_path = format!("/mvp/users/{}/repos/repo", user_id);
+        let mut new_server = create_server(vec![
+            HttpMockParams {
+                method: &Method::PUT,
+                path: pkarr_path.as_str(),
+                status: 200,
+                body: &b"ok".to_vec(),
+                headers: vec![("Set-Cookie", "sessionId=new_signup")],
+            },
+            HttpMockParams {
+                method: &Method::PUT,
+                path: repo_path.as_str(),
+                status: 200,
+                body: &b"ok".to_vec(),
+                headers: vec![],
+            },
+            HttpMockParams {
+                method: &Method::PUT,
+                path: &format!("{}/b", repo_path),
+                status: 507,
+                body: &b"quota exceeded".to_vec(),
+                headers: vec![],
+            },
+        ]);
+        add_challenge_mock(&mut new_server);
+        let new_url = Url::parse(&new_server.url()).unwrap();
+        let a = add_record_mock(&mut new_server, &format!("{}/a", repo_path));
+
+        let mut client = Client::builder()
+            .seed(*seed)
+            .bootstrap(&testnet.bootstrap)
+            .build()
+            .unwrap();
+        let mut migration = client.migration(&new_url, &["repo"], true).unwrap();
+        assert_eq!(migration.from, old_url.to_string());
+
+        // The new homeserver fails to store the second record
+        let err = client.migrate(&mut migration).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::FailedToMigrate(MigrationError::FailedToCopy { ref path, .. }) if path == "repo/b"
+        ));
+        assert_eq!(err.http_error().unwrap().status(), Some(507));
+        assert_eq!(err.kind(), Some(HTTPErrorKind::QuotaExceeded));
+        assert_eq!(migration.stage, MigrationStage::Copying);
+        assert_eq!(
+            migration.copied,
+            BTreeSet::from([("repo".to_string(), "a".to_string())])
+        );
+        assert_eq!(*a.lock().unwrap(), b"first");
+        assert_eq!(
+            client
+                .http
+                .sessions()
+                .get(&new_url, &user_id)
+                .unwrap()
+                .value,
+            "new_signup"
+        );
+
+        // Resumed from the saved progress
+        let mut migration = Migration::from_bytes(&migration.to_bytes()).unwrap();
+        let b = add_record_mock(&mut new_server, &b_path);
+        client.migrate(&mut migration).unwrap();
+
+        assert!(migration.is_done());
+        assert_eq!(*b.lock().unwrap(), b"second");
+        assert_eq!(migration.deleted, migration.copied);
+        assert_eq!(client.homeserver_url, Some(new_url.clone()));
+        assert_eq!(
+            Resolver::new(None, Some(&testnet.bootstrap))
+                .resolve_homeserver(&key_pair.public_key(), None)
+                .unwrap(),
+            new_url
+        );
+
+        // Nothing left to do
+        client.migrate(&mut migration).unwrap();
+
+        let mut other = Migration::new("other", &old_url, &new_url, &["repo"], false);
+        assert!(matches!(
+            client.migrate(&mut other),
+            Err(Error::FailedToMigrate(MigrationError::InvalidMigration(_)))
+        ));
+    }
+}
@@ -1,5 +1,17 @@
 #[derive(thiserror::Error, Debug)]
 pub enum ClientError {
+    #[error("Failed to signup: {0}")]
+    FailedToSignup(AuthError),
+
+    #[error("Failed to resolve homeserver: {0}")]
+    FailedToResolveHomeserver(AuthError),
+
+    #[error("Invalid user id: {0}")]
+    InvalidUserId(String),
+
+    #[error("Invalid HTTP configuration: {0}")]
+    InvalidHttpConfig(HTTPError),
+
     #[error("Failed to login: {0}")]
     FailedToLogin(AuthError),
 
@@ -20,6 +32,42 @@ pub enum ClientError {
 
     #[error("Failed to delete data from repository: {0}")]
     FailedToDeleteData(HTTPError),
+
+    #[error("Failed to list data in repository: {0}")]
+    FailedToListData(HTTPError),
+
+    #[error("Failed to query data in repository: {0}")]
+    FailedToQueryData(HTTPError),
+
+    #[error("Invalid response from homeserver: {0}")]
+    InvalidResponse(String),
+
+    #[error("Retrieved data is not valid UTF-8: {0}")]
+    InvalidUtf8(std::string::FromUtf8Error),
+
+    #[error("Failed to save client state: {0}")]
+    FailedToSaveState(StateError),
+
+    #[error("Failed to restore client state: {0}")]
+    FailedToRestoreState(StateError),
+
+    #[error("Invalid recovery phrase: {0}")]
+    InvalidMnemonic(MnemonicError),
+
+    #[error("Failed to encrypt data: {0}")]
+    FailedToEncryptData(EncryptionError),
+
+    #[error("Failed to decrypt data: {0}")]
+    FailedToDecryptData(EncryptionError),
+
+    #[error("Failed to sign data: {0}")]
+    FailedToSignData(SignedRecordError),
+
+    #[error("Failed to verify data: {0}")]
+    FailedToVerifyData(SignedRecordError),
+
+    #[error("Failed to migrate to the new homeserver: {0}")]
+    FailedToMigrate(MigrationError),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -39,6 +87,9 @@ pub enum AuthError {
     #[error("Failed to retrieve session: {0}")]
     FailedToRetrieveSession(HTTPError),
 
+    #[error("Invalid session: {0}")]
+    InvalidSession(String),
+
     #[error("No associated homeserver")]
     NoHomeserver,
 
@@ -47,12 +98,260 @@ pub enum AuthError {
 
     #[error("Failed to logout: {0}")]
     FailedToLogout(HTTPError),
+
+    #[error("Invalid challenge: {0}")]
+    InvalidChallenge(ChallengeError),
+}
+
+impl ClientError {
+    /// Returns underlying HTTP error, if the failure was caused by the homeserver response
+    pub fn http_error(&self) -> Option<&HTTPError> {
+        match self {
+            ClientError::FailedToSignup(e)
+            | ClientError::FailedToResolveHomeserver(e)
+            | ClientError::FailedToLogin(e)
+            | ClientError::FailedToLogout(e)
+            | ClientError::FailedToRetrieveSession(e) => e.http_error(),
+            ClientError::FailedToCreateRepository(e)
+            | ClientError::FailedToStoreData(e)
+            | ClientError::FailedToRetrieveData(e)
+            | ClientError::FailedToDeleteData(e)
+            | ClientError::FailedToListData(e)
+            | ClientError::FailedToQueryData(e)
+            | ClientError::InvalidHttpConfig(e) => Some(e),
+            ClientError::FailedToMigrate(e) => e.http_error(),
+            ClientError::InvalidUserId(_)
+            | ClientError::InvalidResponse(_)
+            | ClientError::InvalidUtf8(_)
+            | ClientError::FailedToSaveState(_)
+            | ClientError::FailedToRestoreState(_)
+            | ClientError::InvalidMnemonic(_)
+            | ClientError::FailedToEncryptData(_)
+            | ClientError::FailedToDecryptData(_)
+            | ClientError::FailedToSignData(_)
+            | ClientError::FailedToVerifyData(_) => None,
+        }
+    }
+
+    /// Returns class of the failed homeserver response
+    pub fn kind(&self) -> Option<HTTPErrorKind> {
+        self.http_error().and_then(HTTPError::kind)
+    }
+}
+
+impl AuthError {
+    /// Returns underlying HTTP error, if the failure was caused by the homeserver response
+    pub fn http_error(&self) -> Option<&HTTPError> {
+        match self {
+            AuthError::FailedToGetChallenge(e)
+            | AuthError::FailedToSendUserSignature(e)
+            | AuthError::FailedToRetrieveSession(e)
+            | AuthError::FailedToLogout(e) => Some(e),
+            AuthError::FailedToResolveHomeserver(_)
+            | AuthError::FailedToPublishHomeserver(_)
+            | AuthError::InvalidSession(_)
+            | AuthError::NoHomeserver
+            | AuthError::NoSession
+            | AuthError::InvalidChallenge(_) => None,
+        }
+    }
+
+    /// Returns class of the failed homeserver response
+    pub fn kind(&self) -> Option<HTTPErrorKind> {
+        self.http_error().and_then(HTTPError::kind)
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum HTTPError {
     #[error("Failed to send HTTP request: {0}")]
     RequestFailed(String),
+
+    #[error("Invalid HTTP client configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Request failed with status {status} {reason}: {body}")]
+    ResponseFailed {
+        status: u16,
+        reason: String,
+        body: String,
+    },
+}
+
+/// Class of the failed homeserver response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HTTPErrorKind {
+    NotFound,
+    /// Not logged in, or the session is no longer valid
+    Unauthorized,
+    /// Logged in, but not allowed to access the resource
+    Forbidden,
+    Conflict,
+    QuotaExceeded,
+    ServerError,
+    Other,
+}
+
+impl HTTPError {
+    /// Returns status code of the response, if the request reached the homeserver
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            HTTPError::RequestFailed(_) | HTTPError::InvalidConfig(_) => None,
+            HTTPError::ResponseFailed { status, .. } => Some(*status),
+        }
+    }
+
+    /// Returns class of the failed response, if the request reached the homeserver
+    pub fn kind(&self) -> Option<HTTPErrorKind> {
+        let kind = match self.status()? {
+            404 => HTTPErrorKind::NotFound,
+            401 => HTTPErrorKind::Unauthorized,
+            403 => HTTPErrorKind::Forbidden,
+            409 => HTTPErrorKind::Conflict,
+            413 | 507 => HTTPErrorKind::QuotaExceeded,
+            500..=599 => HTTPErrorKind::ServerError,
+            _ => HTTPErrorKind::Other,
+        };
+
+        Some(kind)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum StateError {
+    #[error("Failed to read state: {0}")]
+    FailedToRead(std::io::Error),
+
+    #[error("Failed to write state: {0}")]
+    FailedToWrite(std::io::Error),
+
+    #[error("Invalid state: {0}")]
+    InvalidState(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum KeystoreError {
+    #[error("Invalid keystore: {0}")]
+    InvalidFormat(String),
+
+    #[error("Unsupported keystore version: {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Invalid key derivation parameters: {0}")]
+    InvalidParams(String),
+
+    #[error("Invalid passphrase")]
+    InvalidPassphrase,
+
+    #[error("Failed to encrypt seed")]
+    FailedToEncrypt,
+
+    #[error("Public key does not match the seed")]
+    PublicKeyMismatch,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MnemonicError {
+    #[error("Recovery phrase must have 24 words, got {0}")]
+    InvalidWordCount(usize),
+
+    #[error("Unknown word in recovery phrase: {0}")]
+    UnknownWord(String),
+
+    #[error("Invalid recovery phrase checksum")]
+    InvalidChecksum,
+
+    #[error("Invalid recovery phrase: {0}")]
+    InvalidPhrase(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum EncryptionError {
+    #[error("Unsupported envelope version: {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Invalid envelope: {0}")]
+    InvalidEnvelope(String),
+
+    #[error("Failed to decrypt, wrong key or tampered payload")]
+    FailedToDecrypt,
+
+    #[error("No recipients to encrypt for")]
+    NoRecipients,
+
+    #[error("Invalid recipient: {0}")]
+    InvalidRecipient(String),
+
+    #[error("Not a recipient of the payload")]
+    NotARecipient,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SignedRecordError {
+    #[error("Unsupported signed record version: {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Invalid signed record: {0}")]
+    InvalidRecord(String),
+
+    #[error("Record was signed for {found}, but read from {expected}")]
+    PathMismatch { expected: String, found: String },
+
+    #[error("Payload does not match the signed hash")]
+    PayloadHashMismatch,
+
+    #[error("Invalid record signature")]
+    InvalidSignature,
+
+    #[error("Record path is {0} bytes long, at most 65535 are supported")]
+    PathTooLong(usize),
+
+    #[error("Records are signed with the own key, they can't be stored for {0}")]
+    NotAuthor(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MigrationError {
+    #[error("Invalid migration: {0}")]
+    InvalidMigration(String),
+
+    #[error("Failed to register at the new homeserver: {0}")]
+    FailedToRegister(AuthError),
+
+    #[error("Failed to copy {path}: {error}")]
+    FailedToCopy { path: String, error: HTTPError },
+
+    #[error("Failed to verify {path}: {error}")]
+    FailedToVerify { path: String, error: HTTPError },
+
+    #[error("Copy of {0} does not match the original")]
+    CopyMismatch(String),
+
+    #[error("Failed to publish the new homeserver: {0}")]
+    FailedToPublish(AuthError),
+
+    #[error("Failed to delete {path} from the old homeserver: {error}")]
+    FailedToDeleteOld { path: String, error: HTTPError },
+
+    #[error("Invalid response from homeserver: {0}")]
+    InvalidResponse(String),
+}
+
+impl MigrationError {
+    /// Returns underlying HTTP error, if the failure was caused by a homeserver response
+    pub fn http_error(&self) -> Option<&HTTPError> {
+        match self {
+            MigrationError::FailedToRegister(e) | MigrationError::FailedToPublish(e) => {
+                e.http_error()
+            }
+            MigrationError::FailedToCopy { error, .. }
+            | MigrationError::FailedToVerify { error, .. }
+            | MigrationError::FailedToDeleteOld { error, .. } => Some(error),
+            MigrationError::InvalidMigration(_)
+            | MigrationError::CopyMismatch(_)
+            | MigrationError::InvalidResponse(_) => None,
+        }
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -62,6 +361,27 @@ pub enum ChallengeError {
 
     #[error("Invalid signature")]
     InvalidSignature,
+
+    #[error("Unsupported challenge version: {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Invalid challenge length: {0}")]
+    InvalidLength(usize),
+
+    #[error("Challenge expires too far in the future: {0}")]
+    ExpiresTooLate(u64),
+
+    #[error("Challenge audience is not valid UTF-8")]
+    InvalidAudience,
+
+    #[error("Challenge was issued for {found}, expected {expected}")]
+    AudienceMismatch { expected: String, found: String },
+
+    #[error("Unknown or already used challenge")]
+    UnknownChallenge,
+
+    #[error("Failed to access challenge storage: {0}")]
+    StorageFailed(String),
 }
 
 #[derive(thiserror::Error, Debug, Clone)]
@@ -74,4 +394,10 @@ pub enum DHTError {
 
     #[error("No records found")]
     NoRecordsFound,
+
+    #[error("Failed to look up DHT entry: {0}")]
+    LookupFailed(String),
+
+    #[error("Failed to access resolver cache: {0}")]
+    FailedToAccessCache(String),
 }
@@ -1,6 +1,9 @@
 pub mod client;
-mod error;
-mod transport;
+pub mod error;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod state;
+pub mod transport;
 mod utils;
 
 #[cfg(test)]
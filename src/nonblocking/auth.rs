@@ -0,0 +1,327 @@
+use crate::error::{AuthError as Error, HTTPErrorKind};
+use crate::nonblocking::http::HttpClient;
+use crate::nonblocking::resolver::Resolver;
+use crate::transport::auth::SigType;
+use crate::transport::challenge::{Challenge, ChallengePolicy};
+use crate::transport::cookies::SessionCookie;
+use crate::transport::crypto::{zeroize, DeterministicKeyGen, Keypair, PublicKey};
+use crate::transport::http::{HeaderMap, Method, Url};
+use crate::transport::session::Session;
+
+/// Async version of [`crate::transport::auth::Auth`]
+pub struct Auth {
+    pub homeserver_url: Option<Url>,
+    /// User who signed up or logged in last, the one [`Auth::session`] is examined for
+    pub user_id: Option<String>,
+    pub http: HttpClient,
+    /// Bounds on the expiration of challenges signed at signup and login
+    pub challenge_policy: ChallengePolicy,
+    /// Whether the homeserver was given rather than resolved, a given one is never re-resolved
+    configured: bool,
+    resolver: Resolver,
+}
+
+impl Auth {
+    pub fn new(resolver: Resolver, http: HttpClient, homeserver_url: Option<Url>) -> Auth {
+        Auth {
+            resolver,
+            user_id: None,
+            http,
+            challenge_policy: ChallengePolicy::default(),
+            configured: homeserver_url.is_some(),
+            homeserver_url,
+        }
+    }
+
+    /// Resolve the homeserver of the user, unless it was given at creation
+    ///
+    /// A resolved homeserver is looked up through the resolver on every call, so it follows the
+    /// user to a new homeserver once the cached record expires or is invalidated.
+    pub async fn resolve_homeserver(
+        &mut self,
+        public_key: &PublicKey,
+        dht_relay_url: Option<&Url>,
+    ) -> Result<Url, Error> {
+        let url = self.homeserver(public_key, dht_relay_url).await?;
+        self.homeserver_url = Some(url.clone());
+
+        Ok(url)
+    }
+
+    /// Same as [`Auth::resolve_homeserver`], without remembering the resolved homeserver, so it
+    /// can be called concurrently
+    pub async fn homeserver(
+        &self,
+        public_key: &PublicKey,
+        dht_relay_url: Option<&Url>,
+    ) -> Result<Url, Error> {
+        if let (true, Some(url)) = (self.configured, &self.homeserver_url) {
+            return Ok(url.clone());
+        }
+
+        self.resolver
+            .resolve_homeserver(public_key, dht_relay_url)
+            .await
+            .map_err(Error::FailedToResolveHomeserver)
+    }
+
+    /// Create a new account at the config homeserver
+    pub async fn signup(
+        &mut self,
+        seed: &[u8; 32],
+        dht_relay_url: Option<&Url>,
+    ) -> Result<String, Error> {
+        let key_pair: &Keypair = &DeterministicKeyGen::generate(Some(seed));
+        let user_id = self
+            .send_user_root_signature(&SigType::Signup, key_pair, dht_relay_url)
+            .await?;
+
+        self.resolve_homeserver(&key_pair.public_key(), dht_relay_url)
+            .await?;
+
+        // Re-publish the homeserver url
+        if let Err(e) = self
+            .resolver
+            .publish(
+                key_pair,
+                &self.homeserver_url.clone().unwrap(),
+                dht_relay_url,
+            )
+            .await
+        {
+            return Err(Error::FailedToPublishHomeserver(e));
+        };
+
+        zeroize(key_pair.secret_key().as_mut());
+
+        Ok(user_id)
+    }
+
+    /// Login to an account at the homeserver
+    pub async fn login(
+        &mut self,
+        seed: &[u8; 32],
+        dht_relay_url: Option<&Url>,
+    ) -> Result<String, Error> {
+        let key_pair = &DeterministicKeyGen::generate(Some(seed));
+        let user_id = self
+            .send_user_root_signature(&SigType::Login, key_pair, dht_relay_url)
+            .await?;
+
+        zeroize(key_pair.secret_key().as_mut());
+
+        Ok(user_id)
+    }
+
+    /// Session cookie of the user at the config homeserver, if the user is logged in
+    pub fn session_id(&self, user_id: &str) -> Option<String> {
+        let homeserver_url = self.homeserver_url.as_ref()?;
+
+        self.http
+            .sessions()
+            .get(homeserver_url, user_id)
+            .map(|cookie| cookie.value)
+    }
+
+    /// All users logged in at the config homeserver
+    pub fn sessions(&self) -> Vec<SessionCookie> {
+        match &self.homeserver_url {
+            None => vec![],
+            Some(homeserver_url) => {
+                let origin = homeserver_url.origin().ascii_serialization();
+                self.http
+                    .sessions()
+                    .list()
+                    .into_iter()
+                    .filter(|cookie| cookie.homeserver == origin)
+                    .collect()
+            }
+        }
+    }
+
+    /// Logout from a specific account at the config homeserver
+    pub async fn logout(&mut self, user_id: &str) -> Result<String, Error> {
+        if self.homeserver_url.is_none() {
+            return Err(Error::NoHomeserver);
+        }
+
+        let session_id = match self.session_id(user_id) {
+            Some(session_id) => session_id,
+            None => return Err(Error::NoSession),
+        };
+
+        let homeserver_url = self.homeserver_url.clone().unwrap();
+        let url = homeserver_url
+            .join(format!("/mvp/session/{}", user_id).as_str())
+            .unwrap();
+
+        match self
+            .http
+            .request(Method::DELETE, url, Some(user_id), None, None)
+            .await
+        {
+            Ok(_) => {
+                self.http.sessions().remove(&homeserver_url, user_id);
+                if self.user_id.as_deref() == Some(user_id) {
+                    self.user_id = None;
+                }
+                Ok(session_id)
+            }
+            Err(e) => Err(Error::FailedToLogout(e)),
+        }
+    }
+
+    /// Examine the current session at the config homeserver
+    pub async fn session(&mut self) -> Result<Option<Session>, Error> {
+        if self.homeserver_url.is_none() {
+            return Err(Error::NoHomeserver);
+        }
+
+        let user_id = match &self.user_id {
+            Some(user_id) if self.session_id(user_id).is_some() => user_id,
+            _ => return Err(Error::NoSession),
+        };
+
+        let url = self
+            .homeserver_url
+            .clone()
+            .unwrap()
+            .join("/mvp/session")
+            .unwrap();
+
+        match self
+            .http
+            .request(Method::GET, url, Some(user_id), None, None)
+            .await
+        {
+            Ok(response) => {
+                let session =
+                    Session::parse(&response).map_err(|e| Error::InvalidSession(e.to_string()))?;
+                if session.is_none() {
+                    self.forget_session();
+                }
+                Ok(session)
+            }
+            Err(e) => {
+                if e.kind() == Some(HTTPErrorKind::Unauthorized) {
+                    self.forget_session();
+                }
+                Err(Error::FailedToRetrieveSession(e))
+            }
+        }
+    }
+
+    /// Drop the session of the current user, after the homeserver stopped recognizing it
+    fn forget_session(&mut self) {
+        if let (Some(homeserver_url), Some(user_id)) = (&self.homeserver_url, &self.user_id) {
+            self.http.sessions().remove(homeserver_url, user_id);
+        }
+    }
+
+    /// Get challenge, sign it and authenticate
+    async fn send_user_root_signature(
+        &mut self,
+        sig_type: &SigType,
+        key_pair: &Keypair,
+        dht_relay_url: Option<&Url>,
+    ) -> Result<String, Error> {
+        let challenge = self
+            .get_valid_challenge(&key_pair.public_key(), dht_relay_url)
+            .await?;
+        let signature = key_pair.sign(&challenge.signable(sig_type)).to_string();
+        let user_id = key_pair.to_z32();
+
+        self.resolve_homeserver(&key_pair.public_key(), dht_relay_url)
+            .await?;
+
+        let path = match sig_type {
+            SigType::Signup => format!("/mvp/users/{}/pkarr", user_id),
+            SigType::Login => format!("/mvp/session/{}", user_id),
+        };
+
+        let url = self
+            .homeserver_url
+            .clone()
+            .unwrap()
+            .join(path.as_str())
+            .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "Content-Type",
+            "application/octet-stream".try_into().unwrap(),
+        );
+        headers.insert("Content-Length", signature.len().into());
+
+        let response = self
+            .http
+            .request(
+                Method::PUT,
+                url,
+                Some(&user_id),
+                Some(&headers),
+                Some(signature.into_bytes()),
+            )
+            .await;
+
+        match response {
+            Ok(_) => {
+                self.user_id = Some(user_id.clone());
+                Ok(user_id)
+            }
+            Err(e) => Err(Error::FailedToSendUserSignature(e)),
+        }
+    }
+
+    /// Get a challenge the policy accepts, requesting a fresh one in place of an expired one
+    async fn get_valid_challenge(
+        &mut self,
+        public_key: &PublicKey,
+        dht_relay_url: Option<&Url>,
+    ) -> Result<Challenge, Error> {
+        let mut retries = self.challenge_policy.retries;
+
+        loop {
+            let challenge = self.get_challenge(public_key, dht_relay_url).await?;
+
+            match challenge.validate(&self.challenge_policy) {
+                Ok(()) => return Ok(challenge),
+                Err(_) if retries > 0 => retries -= 1,
+                Err(e) => return Err(Error::InvalidChallenge(e)),
+            }
+        }
+    }
+
+    /// Get challenge
+    async fn get_challenge(
+        &mut self,
+        public_key: &PublicKey,
+        dht_relay_url: Option<&Url>,
+    ) -> Result<Challenge, Error> {
+        if self.homeserver_url.is_none() {
+            self.homeserver_url = match self
+                .resolver
+                .resolve_homeserver(public_key, dht_relay_url)
+                .await
+            {
+                Ok(url) => Some(url),
+                Err(e) => return Err(Error::FailedToResolveHomeserver(e)),
+            };
+        };
+
+        let homeserver_url = self.homeserver_url.clone().unwrap();
+        let url = homeserver_url.join("/mvp/challenge").unwrap();
+
+        match self.http.request(Method::GET, url, None, None, None).await {
+            Ok(response) => {
+                let challenge =
+                    Challenge::deserialize(&response).map_err(Error::InvalidChallenge)?;
+                challenge
+                    .validate_audience(&homeserver_url)
+                    .map_err(Error::InvalidChallenge)?;
+                Ok(challenge)
+            }
+            Err(e) => Err(Error::FailedToGetChallenge(e)),
+        }
+    }
+}
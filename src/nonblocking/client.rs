@@ -0,0 +1,788 @@
+use crate::error::{AuthError, ClientError as Error};
+use crate::state::{ClientState, StateStore};
+
+use std::time::Duration;
+
+use crate::client::{
+    list_url, query_url, restore_state, Connect, ListOptions, ListPage, QueryEntry, QueryOptions,
+};
+use crate::nonblocking::{auth::Auth, http::HttpClient, resolver::Resolver};
+use crate::transport::{
+    challenge::ChallengePolicy,
+    cookies::{SessionCookie, SessionStore},
+    crypto::{
+        self,
+        mnemonic::{mnemonic_to_seed, seed_to_mnemonic},
+        DeterministicKeyGen,
+    },
+    http::{Bytes, HeaderMap, HttpConfig, Method, Url},
+    resolver::cache::{CacheStrategy, ResolverCache},
+    session::Session,
+};
+
+/// Async version of [`crate::client::Client`]
+///
+/// Listing is paginated through [`ListPage::cursor`], there is no lazy iterator counterpart.
+/// Requests take `&self`, so one client can run several of them concurrently. Homeservers of
+/// other users are looked up through the shared resolver and its cache.
+pub struct Client {
+    pub homeserver_url: Option<Url>, // own homeserver
+    pub user_id: String,             // own user id
+    seed: [u8; 32],
+    auth: Auth, // own homeserver
+    dht_relay: Option<Url>,
+    resolver: Resolver,
+    http: HttpClient,
+}
+
+/// Async version of [`crate::client::ClientBuilder`]
+///
+/// Nothing is sent over the network while building, unless signup or login was requested. If the
+/// homeserver URL is not provided, it is resolved on first use.
+#[derive(Default)]
+pub struct ClientBuilder {
+    seed: Option<[u8; 32]>,
+    mnemonic: Option<String>,
+    homeserver_url: Option<Url>,
+    dht_relay: Option<Url>,
+    bootstrap: Option<Vec<String>>,
+    cache_strategy: CacheStrategy,
+    resolver_cache: Option<ResolverCache>,
+    http_config: HttpConfig,
+    challenge_policy: ChallengePolicy,
+    sessions: Option<SessionStore>,
+    state: Option<ClientState>,
+    connect: Connect,
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seed for pubky key generation, random seed is used if neither seed nor keypair is set
+    pub fn seed(mut self, seed: [u8; 32]) -> Self {
+        self.seed = Some(seed);
+        self.mnemonic = None;
+        self
+    }
+
+    /// Use existing keypair instead of the seed
+    pub fn keypair(mut self, keypair: &crypto::Keypair) -> Self {
+        self.seed = Some(keypair.secret_key());
+        self.mnemonic = None;
+        self
+    }
+
+    /// Recover the seed from a recovery phrase, see [`Client::mnemonic`]
+    pub fn mnemonic(mut self, phrase: &str) -> Self {
+        self.mnemonic = Some(phrase.to_string());
+        self.seed = None;
+        self
+    }
+
+    /// Homeserver of the user, skips homeserver resolution
+    pub fn homeserver_url(mut self, homeserver_url: Url) -> Self {
+        self.homeserver_url = Some(homeserver_url);
+        self
+    }
+
+    /// Relay used for publishing and resolving instead of DHT
+    pub fn dht_relay(mut self, dht_relay: &Url) -> Self {
+        self.dht_relay = Some(dht_relay.clone());
+        self
+    }
+
+    /// Bootstrap nodes of the DHT
+    pub fn bootstrap(mut self, bootstrap: &[String]) -> Self {
+        self.bootstrap = Some(bootstrap.to_vec());
+        self
+    }
+
+    /// How lookups of homeservers use the resolver cache, read-through by default
+    pub fn cache_strategy(mut self, cache_strategy: CacheStrategy) -> Self {
+        self.cache_strategy = cache_strategy;
+        self
+    }
+
+    /// Cache of resolved homeservers, see [`crate::client::ClientBuilder::resolver_cache`]
+    pub fn resolver_cache(mut self, resolver_cache: ResolverCache) -> Self {
+        self.resolver_cache = Some(resolver_cache);
+        self
+    }
+
+    /// Timeout of a single request to a homeserver
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.http_config.timeout = Some(timeout);
+        self
+    }
+
+    /// Tolerated difference between the client and homeserver clocks when checking the
+    /// expiration of auth challenges
+    pub fn clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.challenge_policy.clock_skew = clock_skew;
+        self
+    }
+
+    /// Configuration of the HTTP connection pool shared by all requests of the client
+    pub fn http_config(mut self, http_config: HttpConfig) -> Self {
+        self.http_config = http_config;
+        self
+    }
+
+    /// Session store shared with other clients, e.g. to be logged in to several accounts at once
+    pub fn sessions(mut self, sessions: SessionStore) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// Restore the homeserver and sessions saved by [`Client::save_state`], the seed has to
+    /// belong to the same user
+    pub fn state(mut self, state: ClientState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Sign up at the homeserver while building the client
+    pub fn signup(mut self) -> Self {
+        self.connect = Connect::Signup;
+        self
+    }
+
+    /// Login to the homeserver while building the client
+    pub fn login(mut self) -> Self {
+        self.connect = Connect::Login;
+        self
+    }
+
+    pub async fn build(self) -> Result<Client, Error> {
+        let seed = match &self.mnemonic {
+            Some(phrase) => mnemonic_to_seed(phrase).map_err(Error::InvalidMnemonic)?,
+            None => self
+                .seed
+                .unwrap_or_else(|| crypto::random_bytes(32).try_into().unwrap()),
+        };
+        let user_id = crypto::Keypair::generate(Some(&seed)).to_z32();
+
+        let mut http = HttpClient::new(&self.http_config).map_err(Error::InvalidHttpConfig)?;
+        if let Some(sessions) = self.sessions {
+            http = http.with_sessions(sessions);
+        }
+
+        let mut resolver = Resolver::new(self.dht_relay.as_ref(), self.bootstrap.as_ref())
+            .with_strategy(self.cache_strategy)
+            .with_http(&http);
+        if let Some(cache) = self.resolver_cache {
+            resolver = resolver.with_cache(cache);
+        }
+
+        let mut homeservers = match &self.state {
+            Some(state) => restore_state(state, &user_id, resolver.cache(), http.sessions())?,
+            None => Default::default(),
+        };
+
+        let mut auth = Auth::new(resolver.clone(), http.clone(), self.homeserver_url);
+        if auth.homeserver_url.is_none() {
+            auth.homeserver_url = homeservers.remove(&user_id);
+        }
+        auth.challenge_policy = self.challenge_policy;
+        if self.state.is_some() {
+            // Restored session is only checked on the next call to the homeserver
+            auth.user_id = Some(user_id.clone());
+        }
+
+        match self.connect {
+            Connect::None => (),
+            Connect::Signup => {
+                auth.signup(&seed, self.dht_relay.as_ref())
+                    .await
+                    .map_err(Error::FailedToSignup)?;
+            }
+            Connect::Login => {
+                auth.login(&seed, self.dht_relay.as_ref())
+                    .await
+                    .map_err(Error::FailedToLogin)?;
+            }
+        };
+
+        Ok(Client {
+            homeserver_url: auth.homeserver_url.clone(),
+            seed,
+            auth,
+            user_id,
+            dht_relay: self.dht_relay,
+            resolver,
+            http,
+        })
+    }
+}
+
+impl Client {
+    /// Create a client and sign up at the homeserver
+    pub async fn new(
+        seed: Option<[u8; 32]>,
+        homeserver_url: Option<Url>,
+        dht_relay: Option<&Url>,
+        bootstrap: Option<&Vec<String>>,
+    ) -> Result<Client, Error> {
+        let mut builder = ClientBuilder::new().signup();
+
+        if let Some(seed) = seed {
+            builder = builder.seed(seed);
+        }
+        if let Some(homeserver_url) = homeserver_url {
+            builder = builder.homeserver_url(homeserver_url);
+        }
+        if let Some(dht_relay) = dht_relay {
+            builder = builder.dht_relay(dht_relay);
+        }
+        if let Some(bootstrap) = bootstrap {
+            builder = builder.bootstrap(bootstrap);
+        }
+
+        builder.build().await
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /* GENERAL LOGIC */
+
+    /// Generate a new key pair
+    pub fn generate_keypair(&self) -> crypto::Keypair {
+        crypto::DeterministicKeyGen::generate(Some(&self.seed))
+    }
+
+    /// Get user id
+    pub fn get_user_id(&self) -> String {
+        let keypair = self.generate_keypair();
+        keypair.to_z32()
+    }
+
+    /// Recovery phrase of the seed, restores the client with [`ClientBuilder::mnemonic`]
+    pub fn mnemonic(&self) -> String {
+        seed_to_mnemonic(&self.seed)
+    }
+
+    /* "AUTH" RELATED LOGIC */
+    /// login
+    pub async fn login(&mut self) -> Result<String, Error> {
+        self.auth
+            .login(&self.seed, self.dht_relay.as_ref())
+            .await
+            .map_err(Error::FailedToLogin)
+    }
+
+    /// logout
+    pub async fn logout(&mut self) -> Result<String, Error> {
+        self.auth
+            .logout(&self.user_id)
+            .await
+            .map_err(Error::FailedToLogout)
+    }
+
+    /// session
+    pub async fn session(&mut self) -> Result<Option<Session>, Error> {
+        self.auth
+            .session()
+            .await
+            .map_err(Error::FailedToRetrieveSession)
+    }
+
+    /// All live sessions of this client, see [`crate::client::Client::sessions`]
+    pub fn sessions(&self) -> Vec<SessionCookie> {
+        self.http.sessions().list()
+    }
+
+    /// Revoke the session of the user at the homeserver, see
+    /// [`crate::client::Client::revoke_session`]
+    pub async fn revoke_session(
+        &mut self,
+        homeserver_url: &Url,
+        user_id: &str,
+    ) -> Result<String, Error> {
+        let mut auth = Auth::new(
+            self.resolver.clone(),
+            self.http.clone(),
+            Some(homeserver_url.clone()),
+        );
+
+        auth.logout(user_id).await.map_err(Error::FailedToLogout)
+    }
+
+    /// Homeserver and sessions of the client, see [`crate::client::Client::state`]
+    ///
+    /// Homeservers of other users are not kept, they are resolved again after a restore.
+    pub fn state(&self) -> ClientState {
+        let mut state = ClientState::new(self.user_id.clone());
+
+        if let Some(url) = &self.auth.homeserver_url {
+            state
+                .homeservers
+                .insert(self.user_id.clone(), url.to_string());
+            let entry = crypto::PublicKey::try_from(self.user_id.as_str())
+                .ok()
+                .and_then(|public_key| self.resolver.cache().entry(&public_key));
+            if let Some(entry) = entry {
+                state
+                    .expires_at
+                    .insert(self.user_id.clone(), entry.expires_at);
+            }
+        }
+        state.sessions = self.http.sessions().list();
+
+        state
+    }
+
+    /// Save the client state, so it can be restored with [`ClientBuilder::state`] after a restart
+    pub fn save_state(&self, store: &impl StateStore) -> Result<(), Error> {
+        store.save(&self.state()).map_err(Error::FailedToSaveState)
+    }
+
+    /* "REPOS" RELATED LOGIC */
+
+    /// Create repository for user
+    pub async fn create(&self, user_id: &str, repo_name: &str) -> Result<(), Error> {
+        let url = self
+            .url(
+                user_id,
+                &format!("/mvp/users/{}/repos/{}", user_id, repo_name),
+            )
+            .await?;
+
+        self.http
+            .request(Method::PUT, url, Some(&self.user_id), None, None)
+            .await
+            .map(|_| ())
+            .map_err(Error::FailedToCreateRepository)
+    }
+
+    /// Put data into user's repository and return URL to this repo
+    pub async fn put(
+        &self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+        payload: &str,
+    ) -> Result<Url, Error> {
+        self.put_bytes(user_id, repo_name, path, payload.as_bytes())
+            .await
+    }
+
+    /// Put binary data into user's repository and return URL to this repo
+    pub async fn put_bytes(
+        &self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+        payload: &[u8],
+    ) -> Result<Url, Error> {
+        let url = self
+            .url(
+                user_id,
+                &format!("/mvp/users/{}/repos/{}/{}", user_id, repo_name, path),
+            )
+            .await?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "Content-Type",
+            "application/octet-stream".try_into().unwrap(),
+        );
+        headers.insert("Content-Length", payload.len().into());
+
+        self.http
+            .request(
+                Method::PUT,
+                url.clone(),
+                Some(&self.user_id),
+                Some(&headers),
+                Some(payload.to_vec()),
+            )
+            .await
+            .map(|_| url)
+            .map_err(Error::FailedToStoreData)
+    }
+
+    /// Get data from user's repository
+    pub async fn get(&self, user_id: &str, repo_name: &str, path: &str) -> Result<String, Error> {
+        let body = self.get_bytes(user_id, repo_name, path).await?;
+
+        String::from_utf8(body.to_vec()).map_err(Error::InvalidUtf8)
+    }
+
+    /// Get binary data from user's repository
+    pub async fn get_bytes(
+        &self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+    ) -> Result<Bytes, Error> {
+        let url = self
+            .url(
+                user_id,
+                &format!("/mvp/users/{}/repos/{}/{}", user_id, repo_name, path),
+            )
+            .await?;
+
+        self.http
+            .request(Method::GET, url, Some(&self.user_id), None, None)
+            .await
+            .map_err(Error::FailedToRetrieveData)
+    }
+
+    /// Delete data from user's repository
+    pub async fn delete(&self, user_id: &str, repo_name: &str, path: &str) -> Result<(), Error> {
+        let url = self
+            .url(
+                user_id,
+                &format!("/mvp/users/{}/repos/{}/{}", user_id, repo_name, path),
+            )
+            .await?;
+
+        self.http
+            .request(Method::DELETE, url, Some(&self.user_id), None, None)
+            .await
+            .map(|_| ())
+            .map_err(Error::FailedToDeleteData)
+    }
+
+    /// List data in user's repository under given path, returning a single page of entries
+    pub async fn list(
+        &self,
+        user_id: &str,
+        repo_name: &str,
+        path: &str,
+        opts: Option<ListOptions>,
+    ) -> Result<ListPage, Error> {
+        let url = list_url(
+            &self.homeserver(user_id).await?,
+            user_id,
+            repo_name,
+            path,
+            &opts.unwrap_or_default(),
+        );
+
+        let body = self
+            .http
+            .request(Method::GET, url, Some(&self.user_id), None, None)
+            .await
+            .map_err(Error::FailedToListData)?;
+
+        serde_json::from_slice(&body).map_err(|e| Error::InvalidResponse(e.to_string()))
+    }
+
+    /// Query key/value pairs in user's repository within the given key range
+    pub async fn query(
+        &self,
+        user_id: &str,
+        repo_name: &str,
+        opts: Option<QueryOptions>,
+    ) -> Result<Vec<QueryEntry>, Error> {
+        let url = query_url(
+            &self.homeserver(user_id).await?,
+            user_id,
+            repo_name,
+            &opts.unwrap_or_default(),
+        );
+
+        let body = self
+            .http
+            .request(Method::GET, url, Some(&self.user_id), None, None)
+            .await
+            .map_err(Error::FailedToQueryData)?;
+
+        serde_json::from_slice(&body).map_err(|e| Error::InvalidResponse(e.to_string()))
+    }
+
+    /// Forget the resolved homeserver of the user, see [`crate::client::Client::invalidate`]
+    pub fn invalidate(&self, user_id: &str) -> Result<(), Error> {
+        let public_key = crypto::PublicKey::try_from(user_id)
+            .map_err(|_| Error::InvalidUserId(user_id.to_string()))?;
+        self.resolver.invalidate(&public_key);
+
+        Ok(())
+    }
+
+    /// Get homeserver of the user, resolving it through the shared resolver
+    async fn homeserver(&self, user_id: &str) -> Result<Url, Error> {
+        let public_key = crypto::PublicKey::try_from(user_id)
+            .map_err(|_| Error::InvalidUserId(user_id.to_string()))?;
+
+        if user_id == self.user_id {
+            return self
+                .auth
+                .homeserver(&public_key, self.dht_relay.as_ref())
+                .await
+                .map_err(Error::FailedToResolveHomeserver);
+        }
+
+        self.resolver
+            .resolve_homeserver(&public_key, self.dht_relay.as_ref())
+            .await
+            .map_err(|e| Error::FailedToResolveHomeserver(AuthError::FailedToResolveHomeserver(e)))
+    }
+
+    async fn url(&self, user_id: &str, path: &str) -> Result<Url, Error> {
+        Ok(self.homeserver(user_id).await?.join(path).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::StateError;
+    use crate::state::FileStateStore;
+    use crate::test_utils::*;
+    use crate::transport::crypto::Keypair;
+    use crate::transport::resolver::store::PacketStore;
+    use mainline::dht::Testnet;
+
+    #[test]
+    fn test_client_walk_through() {
+        let testnet = Testnet::new(10);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let repo_name = "test_repo";
+        let folder_path = "test_path";
+        let data = "test_payload";
+
+        let mut server = create_homeserver_mock(
+            user_id.to_string(),
+            repo_name.to_string(),
+            folder_path.to_string(),
+            data.to_string(),
+        );
+        let path = format!("/mvp/users/{}/repos/{}/?limit=1", user_id, repo_name);
+        let body = br#"{"entries":[{"path":"test_path"}],"cursor":null}"#.to_vec();
+        add_mocks(
+            &mut server,
+            vec![HttpMockParams {
+                method: &Method::GET,
+                path: path.as_str(),
+                status: 200,
+                body: &body,
+                headers: vec![],
+            }],
+        );
+
+        let _ = publish_url(
+            &key_pair,
+            &Url::parse(&server.url()).unwrap(),
+            &testnet.bootstrap,
+        );
+
+        runtime.block_on(async {
+            let mut client = Client::new(Some(*seed), None, None, Some(&testnet.bootstrap))
+                .await
+                .unwrap();
+            assert_eq!(client.user_id, user_id);
+            assert_eq!(
+                client.homeserver_url,
+                Some(Url::parse(&server.url()).unwrap())
+            );
+
+            client.create(&user_id, repo_name).await.unwrap();
+
+            let url = client
+                .put(&user_id, repo_name, folder_path, data)
+                .await
+                .unwrap();
+            assert_eq!(
+                url.path(),
+                format!("/mvp/users/{}/repos/{}/{}", user_id, repo_name, folder_path)
+            );
+
+            // One client runs several requests at once
+            let (result, bytes) = tokio::join!(
+                client.get(&user_id, repo_name, folder_path),
+                client.get_bytes(&user_id, repo_name, folder_path)
+            );
+            assert_eq!(result.unwrap(), data);
+            assert_eq!(bytes.unwrap(), data.as_bytes());
+
+            let page = client
+                .list(
+                    &user_id,
+                    repo_name,
+                    "",
+                    Some(ListOptions {
+                        limit: Some(1),
+                        ..Default::default()
+                    }),
+                )
+                .await
+                .unwrap();
+            assert_eq!(page.entries[0].path, folder_path);
+            assert_eq!(page.cursor, None);
+
+            client
+                .delete(&user_id, repo_name, folder_path)
+                .await
+                .unwrap();
+            assert_eq!(client.logout().await.unwrap(), "delete_data");
+        });
+    }
+
+    #[test]
+    fn test_client_builder_offline() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let homeserver_url = Url::parse("http://localhost:1").unwrap();
+
+        runtime.block_on(async {
+            let client = Client::builder()
+                .seed(*seed)
+                .homeserver_url(homeserver_url.clone())
+                .build()
+                .await
+                .unwrap();
+            assert_eq!(client.user_id, key_pair.to_z32());
+            assert_eq!(client.homeserver_url, Some(homeserver_url.clone()));
+            assert!(client.sessions().is_empty());
+
+            let client = Client::builder()
+                .mnemonic(&client.mnemonic())
+                .build()
+                .await
+                .unwrap();
+            assert_eq!(client.user_id, key_pair.to_z32());
+            assert_eq!(client.homeserver_url, None);
+
+            let result = Client::builder()
+                .keypair(&key_pair)
+                .homeserver_url(homeserver_url)
+                .timeout(Duration::from_secs(1))
+                .signup()
+                .build()
+                .await;
+            assert!(matches!(result, Err(Error::FailedToSignup(_))));
+        });
+    }
+
+    #[test]
+    fn test_client_builder_login() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let seed = b"it is a seed for key generation!";
+        let user_id = Keypair::generate(Some(seed)).to_z32();
+
+        let server = create_homeserver_mock(
+            user_id.to_string(),
+            "repo_name".to_string(),
+            "folder_path".to_string(),
+            "data".to_string(),
+        );
+        let url = Url::parse(&server.url()).unwrap();
+        let dir = TempDir::new("pdk-client-state");
+        let store = FileStateStore::new(dir.join("state.json"));
+
+        runtime.block_on(async {
+            let sessions = SessionStore::new();
+            let client = Client::builder()
+                .seed(*seed)
+                .homeserver_url(url.clone())
+                .sessions(sessions.clone())
+                .clock_skew(Duration::from_secs(5))
+                .login()
+                .build()
+                .await
+                .unwrap();
+            assert_eq!(sessions.list()[0].value, "send_signature_login");
+            client.save_state(&store).unwrap();
+
+            // No signup or login, the homeserver and session come from the saved state
+            let mut client = Client::builder()
+                .seed(*seed)
+                .state(store.load().unwrap().unwrap())
+                .build()
+                .await
+                .unwrap();
+            assert_eq!(client.homeserver_url, Some(url.clone()));
+            assert_eq!(client.sessions()[0].value, "send_signature_login");
+            assert!(client.session().await.unwrap().is_some());
+            assert_eq!(
+                client
+                    .get(&user_id, "repo_name", "folder_path")
+                    .await
+                    .unwrap(),
+                "data"
+            );
+
+            // State of another user is rejected
+            let err = Client::builder()
+                .seed(*b"it is another seed for key gen!!")
+                .state(client.state())
+                .build()
+                .await
+                .err()
+                .unwrap();
+            assert!(matches!(
+                err,
+                Error::FailedToRestoreState(StateError::InvalidState(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn test_client_builder_resolver_cache() {
+        let testnet = Testnet::new(10);
+        let other_testnet = Testnet::new(3);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let dir = TempDir::new("pdk-packets");
+
+        let seed = b"it is a seed for key generation!";
+        let key_pair: Keypair = DeterministicKeyGen::generate(Some(seed));
+        let user_id = key_pair.to_z32();
+        let server = create_homeserver_mock(
+            user_id.to_string(),
+            "test_repo".to_string(),
+            "test_path".to_string(),
+            "test_payload".to_string(),
+        );
+        let url = Url::parse(&server.url()).unwrap();
+        let _ = publish_url(&key_pair, &url, &testnet.bootstrap);
+
+        runtime.block_on(async {
+            let cache =
+                ResolverCache::new().with_packet_store(PacketStore::open(dir.path()).unwrap());
+            let client = Client::builder()
+                .seed(*seed)
+                .bootstrap(&testnet.bootstrap)
+                .cache_strategy(CacheStrategy::ReadThrough)
+                .resolver_cache(cache)
+                .build()
+                .await
+                .unwrap();
+            assert_eq!(
+                client
+                    .get(&user_id, "test_repo", "test_path")
+                    .await
+                    .unwrap(),
+                "test_payload"
+            );
+
+            // Restarted client resolves from disk, the packet is not published on its DHT
+            let cache =
+                ResolverCache::new().with_packet_store(PacketStore::open(dir.path()).unwrap());
+            let client = Client::builder()
+                .seed(*seed)
+                .bootstrap(&other_testnet.bootstrap)
+                .resolver_cache(cache)
+                .build()
+                .await
+                .unwrap();
+            assert_eq!(
+                client
+                    .get(&user_id, "test_repo", "test_path")
+                    .await
+                    .unwrap(),
+                "test_payload"
+            );
+        });
+    }
+}
@@ -0,0 +1,160 @@
+use crate::error::HTTPError as Error;
+use crate::transport::cookies::SessionStore;
+use crate::transport::http::{Bytes, HeaderMap, HttpConfig, Method, Url, DEFAULT_USER_AGENT};
+use reqwest::header::COOKIE;
+use reqwest::Client;
+
+/// Async version of [`crate::transport::http::HttpClient`]
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    sessions: SessionStore,
+}
+
+impl HttpClient {
+    pub fn new(config: &HttpConfig) -> Result<HttpClient, Error> {
+        let mut builder = Client::builder().user_agent(
+            config
+                .user_agent
+                .clone()
+                .unwrap_or(DEFAULT_USER_AGENT.to_string()),
+        );
+
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = config.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(proxy) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy.clone())
+                .map_err(|e| Error::InvalidConfig(e.to_string()))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(max_idle_connections) = config.max_idle_connections {
+            builder = builder.pool_max_idle_per_host(max_idle_connections);
+        }
+
+        match builder.build() {
+            Ok(client) => Ok(HttpClient {
+                client,
+                sessions: SessionStore::new(),
+            }),
+            Err(e) => Err(Error::InvalidConfig(e.to_string())),
+        }
+    }
+
+    /// Use the given session store instead of a fresh one
+    pub fn with_sessions(mut self, sessions: SessionStore) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    /// Pooled client the requests are sent with, e.g. to share the pool with the resolver
+    pub(crate) fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Sessions of all users known to this transport
+    pub fn sessions(&self) -> &SessionStore {
+        &self.sessions
+    }
+
+    /// See [`crate::transport::http::HttpClient::request`]
+    pub async fn request(
+        &self,
+        method: Method,
+        path: Url,
+        user_id: Option<&str>,
+        headers: Option<&HeaderMap>,
+        body: Option<Vec<u8>>,
+    ) -> Result<Bytes, Error> {
+        let mut request_builder = self.client.request(method, path.clone());
+
+        if let Some(body) = body {
+            request_builder = request_builder.body(body);
+        }
+
+        if let Some(cookie) =
+            user_id.and_then(|user_id| self.sessions.cookie_header(&path, user_id))
+        {
+            request_builder = request_builder.header(COOKIE, cookie);
+        }
+
+        if let Some(headers) = headers {
+            request_builder = request_builder.headers(headers.clone());
+        }
+
+        match request_builder.send().await {
+            Ok(res) => {
+                let status = res.status();
+                if !status.is_success() {
+                    return Err(Error::ResponseFailed {
+                        status: status.as_u16(),
+                        reason: status.canonical_reason().unwrap_or_default().to_string(),
+                        body: String::from_utf8_lossy(&res.bytes().await.unwrap_or_default())
+                            .to_string(),
+                    });
+                }
+
+                if let Some(user_id) = user_id {
+                    self.sessions
+                        .store_response_cookies(&path, user_id, res.cookies());
+                }
+                res.bytes()
+                    .await
+                    .map_err(|err| Error::RequestFailed(err.to_string()))
+            }
+            Err(err) => Err(Error::RequestFailed(err.to_string())),
+        }
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        HttpClient::new(&HttpConfig::default()).expect("Failed to build default HTTP client")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::HTTPErrorKind;
+    use crate::test_utils;
+
+    #[test]
+    fn test_request() {
+        let dummy_test_mock_params = test_utils::HttpMockParams {
+            method: &Method::GET,
+            path: "/test",
+            status: 200,
+            body: &b"test".to_vec(),
+            headers: vec![("Set-Cookie", "sessionId=123")],
+        };
+        let missing_mock_params = test_utils::HttpMockParams {
+            method: &Method::GET,
+            path: "/missing",
+            status: 404,
+            body: &b"not found".to_vec(),
+            headers: vec![],
+        };
+        let server = test_utils::create_server(vec![dummy_test_mock_params, missing_mock_params]);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let http = HttpClient::default();
+
+        let path = Url::parse(&format!("{}/test", server.url())).unwrap();
+
+        let res =
+            runtime.block_on(http.request(Method::GET, path.clone(), Some("user"), None, None));
+
+        assert_eq!(http.sessions().get(&path, "user").unwrap().value, "123");
+        assert_eq!(res.unwrap(), "test");
+
+        let path = Url::parse(&format!("{}/missing", server.url())).unwrap();
+        let res = runtime.block_on(http.request(Method::GET, path, None, None, None));
+
+        let err = res.unwrap_err();
+        assert_eq!(err.status(), Some(404));
+        assert_eq!(err.kind(), Some(HTTPErrorKind::NotFound));
+    }
+}
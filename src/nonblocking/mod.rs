@@ -0,0 +1,7 @@
+// Async counterparts of the blocking client, auth and resolver, enabled with the `async` feature.
+// Requests are sent with the non-blocking reqwest client, relays are queried directly over http
+// and DHT operations are moved to tokio's blocking pool.
+pub mod auth;
+pub mod client;
+pub mod http;
+pub mod resolver;
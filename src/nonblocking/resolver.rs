@@ -0,0 +1,333 @@
+use crate::error::DHTError as Error;
+use crate::nonblocking::http::HttpClient;
+use crate::transport::resolver::cache::{CacheDecision, CacheStrategy, ResolverCache};
+use crate::transport::resolver::{homeserver_key, homeserver_packet, homeserver_ttl};
+use crate::utils::now;
+use pkarr::{Keypair, PkarrClient, PublicKey, SignedPacket};
+use reqwest::{StatusCode, Url};
+
+/// Async version of [`crate::transport::resolver::Resolver`]
+///
+/// Resolver is cheap to clone, clones share the same cache. All methods take `&self`, so lookups
+/// for several users can be run concurrently.
+#[derive(Clone)]
+pub struct Resolver {
+    relay_url: Option<Url>,
+    cache: ResolverCache,
+    strategy: CacheStrategy,
+    bootstrap: Option<Vec<String>>,
+    http_client: reqwest::Client,
+}
+
+impl Resolver {
+    /// Creates a new resolver, if relay_url is None, it will publish to DHT
+    pub fn new(relay_url: Option<&Url>, bootstrap: Option<&Vec<String>>) -> Resolver {
+        Resolver {
+            relay_url: relay_url.cloned(),
+            cache: ResolverCache::new(),
+            strategy: CacheStrategy::default(),
+            bootstrap: bootstrap.cloned(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// Use the cache, e.g. one shared with other resolvers
+    pub fn with_cache(mut self, cache: ResolverCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// How lookups use the cache, read-through by default. Background refreshes are spawned on
+    /// the tokio runtime of the lookup
+    pub fn with_strategy(mut self, strategy: CacheStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Send relay requests through the connection pool of the client
+    pub fn with_http(mut self, http: &HttpClient) -> Self {
+        self.http_client = http.client().clone();
+        self
+    }
+
+    pub fn cache(&self) -> &ResolverCache {
+        &self.cache
+    }
+
+    /// Forget the cached homeserver of the user, e.g. after it moved to another homeserver
+    pub fn invalidate(&self, public_key: &PublicKey) {
+        self.cache.invalidate(public_key);
+    }
+
+    /// Resolves home server url using DHT or relay (with name '_pubky')
+    pub async fn resolve_homeserver(
+        &self,
+        public_key: &PublicKey,
+        relay_url: Option<&Url>,
+    ) -> Result<Url, Error> {
+        match self.strategy.decide(self.cache.entry(public_key), now()) {
+            CacheDecision::Hit(cached) => return cached.into_result(public_key),
+            CacheDecision::HitAndRefresh(cached) => {
+                self.refresh(public_key, relay_url);
+                return cached.into_result(public_key);
+            }
+            CacheDecision::Miss => (),
+        }
+
+        if let Some(url) = self.cache.load(public_key) {
+            return Ok(url);
+        }
+
+        let result = self.resolve_uncached(public_key, relay_url).await;
+        self.cache.update(public_key, result)
+    }
+
+    /// Refreshes the cached entry in a background task
+    fn refresh(&self, public_key: &PublicKey, relay_url: Option<&Url>) {
+        if !self.cache.begin_refresh(public_key) {
+            return;
+        }
+
+        let resolver = self.clone();
+        let public_key = public_key.clone();
+        let relay_url = relay_url.cloned();
+        tokio::spawn(async move {
+            let result = resolver
+                .resolve_uncached(&public_key, relay_url.as_ref())
+                .await;
+            resolver.cache.refreshed(&public_key, result);
+        });
+    }
+
+    /// Looks up the packets of the user and of its homeserver, bypassing the cache
+    async fn resolve_uncached(
+        &self,
+        public_key: &PublicKey,
+        relay_url: Option<&Url>,
+    ) -> Result<(SignedPacket, SignedPacket), Error> {
+        let packet = self.lookup(public_key, relay_url).await?;
+        let homeserver_key = homeserver_key(&packet)?;
+        let homeserver_packet = self.lookup(&homeserver_key, relay_url).await?;
+
+        Ok((packet, homeserver_packet))
+    }
+
+    /// Publish record to relay or DHT
+    pub async fn publish(
+        &self,
+        key_pair: &Keypair,
+        homeserver_url: &Url,
+        relay_url: Option<&Url>,
+    ) -> Result<(), Error> {
+        let signed_packet = homeserver_packet(key_pair, homeserver_url)?;
+        let ttl = homeserver_ttl(&signed_packet, &signed_packet);
+
+        let signed_packet = match relay_url.or(self.relay_url.as_ref()) {
+            Some(relay_url) => {
+                let response = self
+                    .http_client
+                    .put(relay_endpoint(relay_url, signed_packet.public_key())?)
+                    .body(signed_packet.as_relay_request())
+                    .send()
+                    .await
+                    .map_err(|e| Error::EntryNotPublished(e.to_string()))?;
+
+                if !response.status().is_success() {
+                    return Err(Error::EntryNotPublished(response.status().to_string()));
+                }
+                signed_packet
+            }
+            None => {
+                let bootstrap = self.bootstrap.clone();
+                // The packet is handed back, it is kept in the cache afterwards
+                tokio::task::spawn_blocking(move || {
+                    let _ = pkarr_client(bootstrap).publish(&signed_packet);
+                    signed_packet
+                })
+                .await
+                .map_err(|e| Error::EntryNotPublished(e.to_string()))?
+            }
+        };
+
+        self.cache
+            .insert(&key_pair.public_key(), homeserver_url.clone(), ttl);
+        self.cache.store_packets(&signed_packet, &signed_packet);
+
+        Ok(())
+    }
+
+    /// Looks up a public key in the relay or DHT
+    async fn lookup(
+        &self,
+        public_key: &PublicKey,
+        relay_url: Option<&Url>,
+    ) -> Result<SignedPacket, Error> {
+        let not_found = || Error::EntryNotFound(public_key.to_string());
+
+        let entry = match relay_url.or(self.relay_url.as_ref()) {
+            Some(relay_url) => {
+                let response = self
+                    .http_client
+                    .get(relay_endpoint(relay_url, public_key)?)
+                    .send()
+                    .await
+                    .map_err(|e| Error::LookupFailed(e.to_string()))?;
+
+                if response.status() == StatusCode::NOT_FOUND {
+                    None
+                } else if !response.status().is_success() {
+                    return Err(Error::LookupFailed(response.status().to_string()));
+                } else {
+                    let bytes = response
+                        .bytes()
+                        .await
+                        .map_err(|e| Error::LookupFailed(e.to_string()))?;
+                    Some(
+                        SignedPacket::from_relay_response(public_key.clone(), bytes)
+                            .map_err(|e| Error::LookupFailed(e.to_string()))?,
+                    )
+                }
+            }
+            None => {
+                let bootstrap = self.bootstrap.clone();
+                let public_key = public_key.clone();
+                tokio::task::spawn_blocking(move || {
+                    pkarr_client(bootstrap).resolve_most_recent(public_key)
+                })
+                .await
+                .map_err(|e| Error::LookupFailed(e.to_string()))?
+            }
+        };
+
+        entry.ok_or_else(not_found)
+    }
+}
+
+fn pkarr_client(bootstrap: Option<Vec<String>>) -> PkarrClient {
+    match bootstrap {
+        Some(bootstrap) => PkarrClient::builder().bootstrap(&bootstrap).build(),
+        None => PkarrClient::new(),
+    }
+}
+
+fn relay_endpoint(relay_url: &Url, public_key: &PublicKey) -> Result<Url, Error> {
+    let mut url = relay_url.clone();
+    url.path_segments_mut()
+        .map_err(|_| Error::LookupFailed(format!("invalid relay URL {}", relay_url)))?
+        .push(&public_key.to_z32());
+
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_resolve_homeserver_from_dht_concurrently() {
+        use mainline::dht::Testnet;
+        let testnet = Testnet::new(10);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let key_1 = Keypair::random();
+        let key_2 = Keypair::random();
+        let url_1 = Url::parse("https://datastore-1.example.com").unwrap();
+        let url_2 = Url::parse("https://datastore-2.example.com").unwrap();
+
+        let publisher = Resolver::new(None, Some(&testnet.bootstrap));
+        runtime.block_on(async {
+            publisher.publish(&key_1, &url_1, None).await.unwrap();
+            publisher.publish(&key_2, &url_2, None).await.unwrap();
+        });
+
+        let resolver = Resolver::new(None, Some(&testnet.bootstrap));
+        let (public_key_1, public_key_2) = (key_1.public_key(), key_2.public_key());
+        let (res_1, res_2) = runtime.block_on(async {
+            tokio::join!(
+                resolver.resolve_homeserver(&public_key_1, None),
+                resolver.resolve_homeserver(&public_key_2, None)
+            )
+        });
+
+        assert_eq!(res_1.unwrap(), url_1);
+        assert_eq!(res_2.unwrap(), url_2);
+    }
+
+    #[test]
+    fn test_resolve_homeserver_with_http() {
+        use crate::transport::http::HttpConfig;
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let key = Keypair::random();
+        let mut relay = mockito::Server::new();
+        relay
+            .mock("GET", format!("/{}", key.public_key().to_z32()).as_str())
+            .match_header("user-agent", "pdk-test")
+            .with_status(404)
+            .create();
+        let relay_url = Url::parse(&relay.url()).unwrap();
+
+        // Relay is queried with the configured client, the unmatched default agent would fail
+        let http = HttpClient::new(&HttpConfig {
+            user_agent: Some("pdk-test".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let resolver = Resolver::new(Some(&relay_url), None).with_http(&http);
+        assert!(matches!(
+            runtime.block_on(resolver.resolve_homeserver(&key.public_key(), None)),
+            Err(Error::EntryNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_homeserver_stale_while_revalidate() {
+        use crate::transport::resolver::cache::Cached;
+        use mainline::dht::Testnet;
+        let testnet = Testnet::new(10);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let key = Keypair::random();
+        let url = Url::parse("https://datastore.example.com").unwrap();
+        let moved_url = Url::parse("https://other-datastore.example.com").unwrap();
+
+        let resolver = Resolver::new(None, Some(&testnet.bootstrap)).with_strategy(
+            CacheStrategy::StaleWhileRevalidate {
+                max_stale: Duration::from_secs(60),
+            },
+        );
+        let public_key = key.public_key();
+        runtime.block_on(async {
+            Resolver::new(None, Some(&testnet.bootstrap))
+                .publish(&key, &moved_url, None)
+                .await
+                .unwrap();
+            resolver
+                .cache()
+                .insert(&public_key, url.clone(), Duration::ZERO);
+
+            // Expired, still served right away while it is refreshed
+            assert_eq!(
+                resolver
+                    .resolve_homeserver(&public_key, None)
+                    .await
+                    .unwrap(),
+                url
+            );
+            for _ in 0..100 {
+                if resolver.cache().get(&public_key) == Some(Cached::Found(moved_url.clone())) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            assert_eq!(
+                resolver
+                    .resolve_homeserver(&public_key, None)
+                    .await
+                    .unwrap(),
+                moved_url
+            );
+        });
+    }
+}
@@ -0,0 +1,157 @@
+use crate::error::StateError as Error;
+use crate::transport::cookies::SessionCookie;
+use crate::utils::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// Version of the serialized client state
+pub static STATE_VERSION: u8 = 1;
+
+/// Client state surviving process restarts.
+///
+/// The seed is not part of the state, it has to be provided again when the client is restored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientState {
+    pub version: u8,
+    /// Own user id
+    pub user_id: String,
+    /// Resolved homeserver URLs, keyed by user id
+    pub homeservers: HashMap<String, String>,
+    /// Unix timestamps (seconds) the resolved homeservers are cached until, keyed by user id.
+    /// Homeservers without one are cached for [`MAX_TTL`] once restored.
+    ///
+    /// [`MAX_TTL`]: crate::transport::resolver::cache::MAX_TTL
+    #[serde(default)]
+    pub expires_at: HashMap<String, u64>,
+    /// Session cookies of all users known to the client
+    pub sessions: Vec<SessionCookie>,
+}
+
+impl ClientState {
+    pub fn new(user_id: String) -> ClientState {
+        ClientState {
+            version: STATE_VERSION,
+            user_id,
+            homeservers: HashMap::new(),
+            expires_at: HashMap::new(),
+            sessions: vec![],
+        }
+    }
+
+    /// Serializes the state to JSON
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("Failed to serialize client state")
+    }
+
+    /// Deserializes the state from JSON, rejecting unknown versions
+    pub fn from_bytes(bytes: &[u8]) -> Result<ClientState, Error> {
+        let state: ClientState =
+            serde_json::from_slice(bytes).map_err(|e| Error::InvalidState(e.to_string()))?;
+
+        if state.version != STATE_VERSION {
+            return Err(Error::InvalidState(format!(
+                "unsupported version {}",
+                state.version
+            )));
+        }
+
+        Ok(state)
+    }
+}
+
+/// Storage for the client state, implement it to keep the state in a caller-provided place
+pub trait StateStore {
+    /// Loads the state, `None` if nothing was saved yet
+    fn load(&self) -> Result<Option<ClientState>, Error>;
+
+    /// Saves the state, replacing the previous one
+    fn save(&self, state: &ClientState) -> Result<(), Error>;
+}
+
+/// Keeps the client state in a JSON file.
+///
+/// The file holds session cookies, on unix it is only readable by the owner.
+#[derive(Debug, Clone)]
+pub struct FileStateStore {
+    path: PathBuf,
+}
+
+impl FileStateStore {
+    pub fn new(path: impl Into<PathBuf>) -> FileStateStore {
+        FileStateStore { path: path.into() }
+    }
+}
+
+impl StateStore for FileStateStore {
+    fn load(&self) -> Result<Option<ClientState>, Error> {
+        match fs::read(&self.path) {
+            Ok(bytes) => ClientState::from_bytes(&bytes).map(Some),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::FailedToRead(e)),
+        }
+    }
+
+    fn save(&self, state: &ClientState) -> Result<(), Error> {
+        write_atomic(&self.path, &state.to_bytes(), 0o600).map_err(Error::FailedToWrite)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn test_file_state_store() {
+        let dir = TempDir::new("pdk-state");
+        let path = dir.join("state.json");
+        let store = FileStateStore::new(&path);
+
+        assert!(store.load().unwrap().is_none());
+
+        let mut state = ClientState::new("user_id".to_string());
+        state
+            .homeservers
+            .insert("user_id".to_string(), "http://localhost:6287/".to_string());
+        state
+            .expires_at
+            .insert("user_id".to_string(), 1_700_000_000);
+        state.sessions.push(SessionCookie {
+            homeserver: "http://localhost:6287".to_string(),
+            user_id: "user_id".to_string(),
+            value: "session".to_string(),
+            path: "/".to_string(),
+            secure: false,
+            expires_at: None,
+        });
+        store.save(&state).unwrap();
+
+        assert_eq!(store.load().unwrap(), Some(state.clone()));
+
+        // Concurrent saves each write through their own temporary file
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| store.save(&state).unwrap());
+            }
+        });
+        assert_eq!(store.load().unwrap(), Some(state.clone()));
+        // No temporary file is left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        #[cfg(unix)]
+        assert_eq!(
+            std::os::unix::fs::PermissionsExt::mode(&fs::metadata(&path).unwrap().permissions())
+                & 0o777,
+            0o600
+        );
+
+        fs::write(
+            &path,
+            br#"{"version":0,"user_id":"","homeservers":{},"sessions":[]}"#,
+        )
+        .unwrap();
+        assert!(matches!(store.load(), Err(Error::InvalidState(_))));
+    }
+}
@@ -1,10 +1,43 @@
+use crate::transport::crypto::random_bytes;
 use crate::transport::{
-    challenge::Challenge,
+    challenge::{audience, Challenge},
     crypto::Keypair,
     http::{Method, Url},
     resolver::Resolver,
 };
 use crate::utils::now;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Fresh directory in the system temp dir, removed with its content when dropped, so it is
+/// cleaned up even when the test fails
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(prefix: &str) -> TempDir {
+        let path =
+            std::env::temp_dir().join(format!("{}-{}", prefix, z32::encode(&random_bytes(8))));
+        std::fs::create_dir_all(&path).unwrap();
+
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
 
 pub fn publish_url<'a>(
     key_pair: &'a Keypair,
@@ -27,7 +60,12 @@ pub struct HttpMockParams<'a> {
 
 pub fn create_server(params: Vec<HttpMockParams>) -> mockito::ServerGuard {
     let mut server = mockito::Server::new();
+    add_mocks(&mut server, params);
+
+    server
+}
 
+pub fn add_mocks(server: &mut mockito::ServerGuard, params: Vec<HttpMockParams>) {
     for param in params {
         let mut request = server.mock(param.method.as_str(), param.path);
         request = request.with_status(param.status.into());
@@ -37,8 +75,45 @@ pub fn create_server(params: Vec<HttpMockParams>) -> mockito::ServerGuard {
         }
         request.create();
     }
+}
 
+/// Mocks a stored record: PUT replaces its content, GET returns it
+pub fn add_record_mock(server: &mut mockito::ServerGuard, path: &str) -> Arc<Mutex<Vec<u8>>> {
+    let record = Arc::new(Mutex::new(vec![]));
+
+    let stored = record.clone();
+    server
+        .mock("PUT", path)
+        .with_body_from_request(move |request| {
+            *stored.lock().unwrap() = request.body().unwrap().clone();
+            b"ok".to_vec()
+        })
+        .create();
+
+    let stored = record.clone();
     server
+        .mock("GET", path)
+        .with_body_from_request(move |_| stored.lock().unwrap().clone())
+        .create();
+
+    record
+}
+
+/// Mocks the challenge endpoint, issuing challenges bound to the mock server
+pub fn add_challenge_mock(server: &mut mockito::ServerGuard) {
+    let audience = audience(&Url::parse(&server.url()).unwrap());
+    let challenge = Challenge::create(now() + 60, &audience, None);
+
+    add_mocks(
+        server,
+        vec![HttpMockParams {
+            method: &Method::GET,
+            path: "/mvp/challenge",
+            body: &challenge.serialize(),
+            status: 200,
+            headers: vec![],
+        }],
+    );
 }
 
 pub fn create_homeserver_mock(
@@ -47,17 +122,7 @@ pub fn create_homeserver_mock(
     folder_path: String,
     data: String,
 ) -> mockito::ServerGuard {
-    let challenge = Challenge::create(now() + 1000, None);
-
     // AUTH
-    let get_challange_mock_params = HttpMockParams {
-        method: &Method::GET,
-        path: "/mvp/challenge",
-        body: &challenge.serialize(),
-        status: 200,
-        headers: vec![],
-    };
-
     let path = format!("/mvp/users/{}/pkarr", user_id);
     let send_user_root_signature_signup_mock_params = HttpMockParams {
         method: &Method::PUT,
@@ -76,11 +141,15 @@ pub fn create_homeserver_mock(
         body: &b"ok".to_vec(),
     };
 
+    let session = format!(
+        r#"{{"users":{{"{}":{{"permissions":[{{"repo":"*","actions":["read","write","delete"]}}]}}}}}}"#,
+        user_id
+    );
     let get_session_mock_params = HttpMockParams {
         method: &Method::GET,
         path: "/mvp/session",
         headers: vec![("Set-Cookie", "sessionId=get_session")],
-        body: &b"session".to_vec(), // TODO: proper session object
+        body: &session.into_bytes(),
         status: 200,
     };
 
@@ -130,8 +199,7 @@ pub fn create_homeserver_mock(
         body: &b"totally ok".to_vec(),
     };
 
-    create_server(vec![
-        get_challange_mock_params,
+    let mut server = create_server(vec![
         send_user_root_signature_signup_mock_params,
         send_user_root_signature_login_mock_params,
         get_session_mock_params,
@@ -140,5 +208,8 @@ pub fn create_homeserver_mock(
         put_folder_mock_params,
         get_data_mock_params,
         delete_data_mock_params,
-    ])
+    ]);
+    add_challenge_mock(&mut server);
+
+    server
 }
@@ -1,41 +1,102 @@
-use crate::error::AuthError as Error;
-use crate::transport::challenge::Challenge;
+use crate::error::{AuthError as Error, HTTPErrorKind};
+use crate::transport::challenge::{Challenge, ChallengePolicy};
+use crate::transport::cookies::SessionCookie;
 use crate::transport::crypto::{zeroize, DeterministicKeyGen, Keypair, PublicKey};
-use crate::transport::http::{request, HeaderMap, Method, Url};
+use crate::transport::http::{HeaderMap, HttpClient, Method, Url};
 use crate::transport::resolver::Resolver;
+use crate::transport::session::Session;
 
+/// Action authorized by signing a challenge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SigType {
     Signup,
     Login,
 }
 
+impl SigType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SigType::Signup => "signup",
+            SigType::Login => "login",
+        }
+    }
+}
+
 pub struct Auth<'a> {
     pub homeserver_url: Option<Url>,
-    pub session_id: Option<String>,
+    /// User who signed up or logged in last, the one [`Auth::session`] is examined for
+    pub user_id: Option<String>,
+    pub http: HttpClient,
+    /// Bounds on the expiration of challenges signed at signup and login
+    pub challenge_policy: ChallengePolicy,
+    /// Whether the homeserver was given rather than resolved, a given one is never re-resolved
+    configured: bool,
     resolver: Resolver<'a>,
 }
 
 impl Auth<'_> {
-    pub fn new(resolver: Resolver, homeserver_url: Option<Url>) -> Auth {
+    pub fn new(resolver: Resolver, http: HttpClient, homeserver_url: Option<Url>) -> Auth {
         Auth {
             resolver,
-            session_id: None,
+            user_id: None,
+            http,
+            challenge_policy: ChallengePolicy::default(),
+            configured: homeserver_url.is_some(),
             homeserver_url,
         }
     }
 
+    /// Resolve the homeserver of the user, unless it was given at creation
+    ///
+    /// A resolved homeserver is looked up through the resolver on every call, so it follows the
+    /// user to a new homeserver once the cached record expires or is invalidated.
+    pub fn resolve_homeserver(
+        &mut self,
+        public_key: &PublicKey,
+        dht_relay_url: Option<&Url>,
+    ) -> Result<Url, Error> {
+        if let (true, Some(url)) = (self.configured, &self.homeserver_url) {
+            return Ok(url.clone());
+        }
+
+        match self.resolver.resolve_homeserver(public_key, dht_relay_url) {
+            Ok(url) => {
+                self.homeserver_url = Some(url.clone());
+                Ok(url)
+            }
+            Err(e) => Err(Error::FailedToResolveHomeserver(e)),
+        }
+    }
+
     /// Create a new account at the config homeserver
     pub fn signup(
         &mut self,
         seed: &[u8; 32],
         dht_relay_url: Option<&Url>,
+    ) -> Result<String, Error> {
+        let user_id = self.register(seed, dht_relay_url)?;
+        self.publish(seed, dht_relay_url)?;
+
+        Ok(user_id)
+    }
+
+    /// Create a new account at the config homeserver, without pointing the user's identity to it
+    pub fn register(
+        &mut self,
+        seed: &[u8; 32],
+        dht_relay_url: Option<&Url>,
     ) -> Result<String, Error> {
         let key_pair: &Keypair = &DeterministicKeyGen::generate(Some(seed));
-        let user_id = match self.send_user_root_signature(&SigType::Signup, key_pair, dht_relay_url)
-        {
-            Ok(user_id) => user_id,
-            Err(e) => return Err(e),
-        };
+        let user_id = self.send_user_root_signature(&SigType::Signup, key_pair, dht_relay_url)?;
+
+        zeroize(key_pair.secret_key().as_mut());
+
+        Ok(user_id)
+    }
+
+    /// Publish the config homeserver as the homeserver of the user
+    pub fn publish(&mut self, seed: &[u8; 32], dht_relay_url: Option<&Url>) -> Result<(), Error> {
+        let key_pair: &Keypair = &DeterministicKeyGen::generate(Some(seed));
 
         if self.homeserver_url.is_none() {
             self.homeserver_url = match self
@@ -59,56 +120,87 @@ impl Auth<'_> {
 
         zeroize(key_pair.secret_key().as_mut());
 
-        Ok(user_id.to_string())
+        Ok(())
     }
 
     /// Login to an account at the homeserver
     // TODO: add support for login to others homeservers (not part of SDK yet)
     pub fn login(&mut self, seed: &[u8; 32], dht_relay_url: Option<&Url>) -> Result<String, Error> {
         let key_pair = &DeterministicKeyGen::generate(Some(seed));
-        let user_id = match self.send_user_root_signature(&SigType::Login, key_pair, dht_relay_url)
-        {
-            Ok(user_id) => user_id,
-            Err(e) => return Err(e),
-        };
+        let user_id = self.send_user_root_signature(&SigType::Login, key_pair, dht_relay_url)?;
 
         zeroize(key_pair.secret_key().as_mut());
 
         Ok(user_id)
     }
 
+    /// Session cookie of the user at the config homeserver, if the user is logged in
+    pub fn session_id(&self, user_id: &str) -> Option<String> {
+        let homeserver_url = self.homeserver_url.as_ref()?;
+
+        self.http
+            .sessions()
+            .get(homeserver_url, user_id)
+            .map(|cookie| cookie.value)
+    }
+
+    /// All users logged in at the config homeserver
+    pub fn sessions(&self) -> Vec<SessionCookie> {
+        match &self.homeserver_url {
+            None => vec![],
+            Some(homeserver_url) => {
+                let origin = homeserver_url.origin().ascii_serialization();
+                self.http
+                    .sessions()
+                    .list()
+                    .into_iter()
+                    .filter(|cookie| cookie.homeserver == origin)
+                    .collect()
+            }
+        }
+    }
+
     /// Logout from a specific account at the config homeserver
     pub fn logout(&mut self, user_id: &str) -> Result<String, Error> {
         if self.homeserver_url.is_none() {
             return Err(Error::NoHomeserver);
         }
 
-        if self.session_id.is_none() {
-            return Err(Error::NoSession);
-        }
+        let session_id = match self.session_id(user_id) {
+            Some(session_id) => session_id,
+            None => return Err(Error::NoSession),
+        };
 
-        let url = self
-            .homeserver_url
-            .clone()
-            .unwrap()
+        let homeserver_url = self.homeserver_url.clone().unwrap();
+        let url = homeserver_url
             .join(format!("/mvp/session/{}", user_id).as_str())
             .unwrap();
 
-        match request(Method::DELETE, url, &mut self.session_id, None, None) {
-            Ok(_) => Ok(self.session_id.take().unwrap().clone()),
+        match self
+            .http
+            .request(Method::DELETE, url, Some(user_id), None, None)
+        {
+            Ok(_) => {
+                self.http.sessions().remove(&homeserver_url, user_id);
+                if self.user_id.as_deref() == Some(user_id) {
+                    self.user_id = None;
+                }
+                Ok(session_id)
+            }
             Err(e) => Err(Error::FailedToLogout(e)),
         }
     }
 
     /// Examine the current session at the config homeserver
-    pub fn session(&mut self) -> Result<String, Error> {
+    pub fn session(&mut self) -> Result<Option<Session>, Error> {
         if self.homeserver_url.is_none() {
             return Err(Error::NoHomeserver);
         }
 
-        if self.session_id.is_none() {
-            return Err(Error::NoSession);
-        }
+        let user_id = match &self.user_id {
+            Some(user_id) if self.session_id(user_id).is_some() => user_id,
+            _ => return Err(Error::NoSession),
+        };
 
         let url = self
             .homeserver_url
@@ -117,20 +209,31 @@ impl Auth<'_> {
             .join("/mvp/session")
             .unwrap();
 
-        match request(Method::GET, url.clone(), &mut self.session_id, None, None) {
+        match self
+            .http
+            .request(Method::GET, url, Some(user_id), None, None)
+        {
             Ok(response) => {
-                // TODO: proper format of response
-                // {
-                //  users: {
-                //    [userId: string]: {
-                //      permissions: Array<any>
-                //    }
-                //  }
-                // } | null
-                // let session = serde_json::from_str(response).unwrap();
-                Ok(response.to_string())
+                let session =
+                    Session::parse(&response).map_err(|e| Error::InvalidSession(e.to_string()))?;
+                if session.is_none() {
+                    self.forget_session();
+                }
+                Ok(session)
+            }
+            Err(e) => {
+                if e.kind() == Some(HTTPErrorKind::Unauthorized) {
+                    self.forget_session();
+                }
+                Err(Error::FailedToRetrieveSession(e))
             }
-            Err(e) => Err(Error::FailedToRetrieveSession(e)),
+        }
+    }
+
+    /// Drop the session of the current user, after the homeserver stopped recognizing it
+    fn forget_session(&mut self) {
+        if let (Some(homeserver_url), Some(user_id)) = (&self.homeserver_url, &self.user_id) {
+            self.http.sessions().remove(homeserver_url, user_id);
         }
     }
 
@@ -141,8 +244,8 @@ impl Auth<'_> {
         key_pair: &Keypair,
         dht_relay_url: Option<&Url>,
     ) -> Result<String, Error> {
-        let challenge = self.get_challenge(&key_pair.public_key(), None);
-        let signature = key_pair.sign(&challenge.unwrap().signable).to_string();
+        let challenge = self.get_valid_challenge(&key_pair.public_key(), dht_relay_url)?;
+        let signature = key_pair.sign(&challenge.signable(sig_type)).to_string();
         let user_id = key_pair.to_z32();
 
         if self.homeserver_url.is_none() {
@@ -174,20 +277,42 @@ impl Auth<'_> {
         );
         headers.insert("Content-Length", signature.len().into());
 
-        let response = request(
+        let response = self.http.request(
             Method::PUT,
             url.clone(),
-            &mut self.session_id,
+            Some(&user_id),
             Some(&headers),
-            Some(signature.to_string()),
+            Some(signature.into_bytes()),
         );
 
         match response {
-            Ok(_) => Ok(user_id.to_string()),
+            Ok(_) => {
+                self.user_id = Some(user_id.clone());
+                Ok(user_id)
+            }
             Err(e) => Err(Error::FailedToSendUserSignature(e)),
         }
     }
 
+    /// Get a challenge the policy accepts, requesting a fresh one in place of an expired one
+    fn get_valid_challenge(
+        &mut self,
+        public_key: &PublicKey,
+        dht_relay_url: Option<&Url>,
+    ) -> Result<Challenge, Error> {
+        let mut retries = self.challenge_policy.retries;
+
+        loop {
+            let challenge = self.get_challenge(public_key, dht_relay_url)?;
+
+            match challenge.validate(&self.challenge_policy) {
+                Ok(()) => return Ok(challenge),
+                Err(_) if retries > 0 => retries -= 1,
+                Err(e) => return Err(Error::InvalidChallenge(e)),
+            }
+        }
+    }
+
     /// Get challenge
     fn get_challenge(
         &mut self,
@@ -202,15 +327,18 @@ impl Auth<'_> {
             };
         };
 
-        let url = self
-            .homeserver_url
-            .clone()
-            .unwrap()
-            .join("/mvp/challenge")
-            .unwrap();
+        let homeserver_url = self.homeserver_url.clone().unwrap();
+        let url = homeserver_url.join("/mvp/challenge").unwrap();
 
-        match request(Method::GET, url.clone(), &mut self.session_id, None, None) {
-            Ok(response) => Ok(Challenge::deserialize(response.as_bytes())),
+        match self.http.request(Method::GET, url, None, None, None) {
+            Ok(response) => {
+                let challenge =
+                    Challenge::deserialize(&response).map_err(Error::InvalidChallenge)?;
+                challenge
+                    .validate_audience(&homeserver_url)
+                    .map_err(Error::InvalidChallenge)?;
+                Ok(challenge)
+            }
             Err(e) => Err(Error::FailedToGetChallenge(e)),
         }
     }
@@ -219,7 +347,11 @@ impl Auth<'_> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::ChallengeError;
     use crate::test_utils::*;
+    use crate::transport::challenge::audience;
+    use crate::transport::session::Action;
+    use crate::utils::now;
     use mainline::dht::Testnet;
 
     #[test]
@@ -240,7 +372,7 @@ mod test {
         let url = Url::parse(&server.url()).unwrap();
         let resolver = publish_url(&key_pair, &url, &testnet.bootstrap);
 
-        let mut auth = Auth::new(resolver, None);
+        let mut auth = Auth::new(resolver, HttpClient::default(), None);
 
         // TEST SIGNUP
         let user_id = auth.signup(seed, None).unwrap();
@@ -250,16 +382,21 @@ mod test {
             auth.homeserver_url,
             Some(Url::parse(&server.url()).unwrap())
         );
-        assert_eq!(auth.session_id, Some(session_id));
+        assert_eq!(auth.session_id(&user_id), Some(session_id));
 
         // TEST LOGOUT
         let res_session_id = auth.logout(&user_id).unwrap();
-        assert_eq!(auth.session_id, None);
+        assert_eq!(auth.session_id(&user_id), None);
+        assert!(auth.sessions().is_empty());
         assert_eq!(res_session_id, "send_signature_signup");
 
         // TEST SIGNUP AGAIN
         let resolver = Resolver::new(None, Some(&testnet.bootstrap));
-        let mut auth = Auth::new(resolver, Some(Url::parse(&server.url()).unwrap()));
+        let mut auth = Auth::new(
+            resolver,
+            HttpClient::default(),
+            Some(Url::parse(&server.url()).unwrap()),
+        );
 
         let got_user_id = auth.signup(seed, None).unwrap();
         let session_id = "send_signature_signup".to_string();
@@ -268,11 +405,12 @@ mod test {
             auth.homeserver_url,
             Some(Url::parse(&server.url()).unwrap())
         );
-        assert_eq!(auth.session_id, Some(session_id));
+        assert_eq!(auth.session_id(&user_id), Some(session_id));
 
         // TEST LOGOUT
         let res_session_id = auth.logout(&user_id).unwrap();
-        assert_eq!(auth.session_id, None);
+        assert_eq!(auth.session_id(&user_id), None);
+        assert!(auth.sessions().is_empty());
         assert_eq!(res_session_id, "send_signature_signup");
 
         // TEST LOGIN
@@ -283,16 +421,91 @@ mod test {
             auth.homeserver_url,
             Some(Url::parse(&server.url()).unwrap())
         );
-        assert_eq!(auth.session_id, Some(session_id));
+        assert_eq!(auth.session_id(&user_id), Some(session_id));
 
         // TEST SESSION
-        let session = auth.session().unwrap();
+        let session = auth.session().unwrap().unwrap();
         let session_id = "get_session".to_string();
-        assert_eq!(session, "session".to_string());
-        assert_eq!(auth.session_id, Some(session_id));
+        assert!(session.has_permission(&user_id, "repo_name", Action::Write));
+        assert_eq!(auth.session_id(&user_id), Some(session_id));
         assert_eq!(
             auth.homeserver_url,
             Some(Url::parse(&server.url()).unwrap())
         );
     }
+
+    #[test]
+    fn auth_malformed_challenge() {
+        let seed = b"it is a seed for key generation!";
+        let server = create_server(vec![HttpMockParams {
+            method: &Method::GET,
+            path: "/mvp/challenge",
+            status: 200,
+            body: &b"garbled".to_vec(),
+            headers: vec![],
+        }]);
+
+        let mut auth = Auth::new(
+            Resolver::new(None, None),
+            HttpClient::default(),
+            Some(Url::parse(&server.url()).unwrap()),
+        );
+
+        assert!(matches!(
+            auth.login(seed, None),
+            Err(Error::InvalidChallenge(_))
+        ));
+    }
+
+    #[test]
+    fn auth_challenge_retry() {
+        let seed = b"it is a seed for key generation!";
+        let user_id = Keypair::generate(Some(seed)).to_z32();
+        let mut server = create_server(vec![]);
+        let url = Url::parse(&server.url()).unwrap();
+        let audience = audience(&url);
+
+        // Expired challenge is replaced by a fresh one
+        server
+            .mock("GET", "/mvp/challenge")
+            .with_body(Challenge::create(now() - 600, &audience, None).serialize())
+            .expect(1)
+            .create();
+        server
+            .mock("GET", "/mvp/challenge")
+            .with_body(Challenge::create(now() + 60, &audience, None).serialize())
+            .create();
+        server
+            .mock("PUT", format!("/mvp/session/{}", user_id).as_str())
+            .with_header("Set-Cookie", "sessionId=send_signature_login")
+            .create();
+
+        let mut auth = Auth::new(Resolver::new(None, None), HttpClient::default(), Some(url));
+        assert_eq!(auth.login(seed, None).unwrap(), user_id);
+
+        // Homeserver keeps handing out challenges expiring too late
+        server.reset();
+        server
+            .mock("GET", "/mvp/challenge")
+            .with_body(Challenge::create(now() + 3600, &audience, None).serialize())
+            .expect(3)
+            .create();
+        assert!(matches!(
+            auth.login(seed, None),
+            Err(Error::InvalidChallenge(ChallengeError::ExpiresTooLate(_)))
+        ));
+
+        // Challenge issued by another homeserver
+        server.reset();
+        server
+            .mock("GET", "/mvp/challenge")
+            .with_body(Challenge::create(now() + 60, "https://other.example", None).serialize())
+            .create();
+        assert!(matches!(
+            auth.login(seed, None),
+            Err(Error::InvalidChallenge(
+                ChallengeError::AudienceMismatch { .. }
+            ))
+        ));
+    }
 }
@@ -0,0 +1,321 @@
+use crate::error::ChallengeError as Error;
+use crate::transport::auth::SigType;
+use crate::transport::crypto;
+use crate::transport::http::Url;
+use crate::utils::now;
+use std::time::Duration;
+
+pub mod store;
+
+/// Challenge issued by a homeserver, signed by the user to authenticate.
+///
+/// The signature covers the homeserver (`audience`) and the action it authorizes, so it can't be
+/// replayed against another homeserver or for another action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    pub value: [u8; 32],
+    pub expires_at: u64,
+    /// Origin of the homeserver which issued the challenge
+    pub audience: String,
+}
+
+static CONTEXT: &str = "pubky:homeserver:challenge";
+
+/// Version of the challenge wire format, the first byte of a serialized challenge
+pub static CHALLENGE_VERSION: u8 = 2;
+
+// version, value, expiration, audience length
+const HEADER_LEN: usize = 1 + 32 + 8 + 2;
+
+/// Bounds on the expiration of a challenge the client agrees to sign
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChallengePolicy {
+    /// Tolerated difference between the client and homeserver clocks
+    pub clock_skew: Duration,
+    /// Longest lifetime of a challenge, later expirations are rejected
+    pub max_lifetime: Duration,
+    /// Fresh challenges requested after an invalid one, before giving up
+    pub retries: u32,
+}
+
+impl Default for ChallengePolicy {
+    fn default() -> Self {
+        ChallengePolicy {
+            clock_skew: Duration::from_secs(30),
+            max_lifetime: Duration::from_secs(600),
+            retries: 2,
+        }
+    }
+}
+
+/// Audience of the challenges issued by the homeserver, its origin
+pub fn audience(homeserver_url: &Url) -> String {
+    homeserver_url.origin().ascii_serialization()
+}
+
+impl Challenge {
+    pub fn new(value: [u8; 32], expires_at: u64, audience: &str) -> Self {
+        Self {
+            value,
+            expires_at,
+            audience: audience.to_string(),
+        }
+    }
+
+    pub fn create(expires_at: u64, audience: &str, challenge: Option<[u8; 32]>) -> Self {
+        // Lazily generate a challenge if none is provided
+        let challenge = challenge.unwrap_or_else(|| {
+            crypto::random_bytes(32)
+                .try_into()
+                .expect("Failed to generate challenge")
+        });
+
+        Self::new(challenge, expires_at, audience)
+    }
+
+    /// Serializes the challenge as
+    /// `version || value || expires_at || audience length || audience`
+    pub fn serialize(&self) -> Vec<u8> {
+        let audience = self.audience.as_bytes();
+        let audience_len = u16::try_from(audience.len()).expect("Audience is too long");
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + audience.len());
+        bytes.push(CHALLENGE_VERSION);
+        bytes.extend_from_slice(&self.value);
+        bytes.extend_from_slice(&self.expires_at.to_be_bytes());
+        bytes.extend_from_slice(&audience_len.to_be_bytes());
+        bytes.extend_from_slice(audience);
+
+        bytes
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        match bytes.first() {
+            None => return Err(Error::InvalidLength(0)),
+            Some(version) if *version != CHALLENGE_VERSION => {
+                return Err(Error::UnsupportedVersion(*version))
+            }
+            Some(_) => (),
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::InvalidLength(bytes.len()));
+        }
+
+        let value: [u8; 32] = bytes[1..33].try_into().unwrap();
+        let expires_at = u64::from_be_bytes(bytes[33..41].try_into().unwrap());
+        let audience_len = u16::from_be_bytes(bytes[41..43].try_into().unwrap()) as usize;
+        if bytes.len() != HEADER_LEN + audience_len {
+            return Err(Error::InvalidLength(bytes.len()));
+        }
+        let audience =
+            std::str::from_utf8(&bytes[HEADER_LEN..]).map_err(|_| Error::InvalidAudience)?;
+
+        Ok(Self::new(value, expires_at, audience))
+    }
+
+    pub fn expired(&self) -> bool {
+        self.expires_at <= now()
+    }
+
+    /// Checks the challenge is not expired and doesn't expire later than the policy allows
+    pub fn validate(&self, policy: &ChallengePolicy) -> Result<(), Error> {
+        let now = now();
+        let clock_skew = policy.clock_skew.as_secs();
+
+        if self.expires_at.saturating_add(clock_skew) <= now {
+            return Err(Error::Expired);
+        }
+        if self.expires_at > now + policy.max_lifetime.as_secs() + clock_skew {
+            return Err(Error::ExpiresTooLate(self.expires_at));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the challenge was issued by the homeserver the client is talking to
+    pub fn validate_audience(&self, homeserver_url: &Url) -> Result<(), Error> {
+        let expected = audience(homeserver_url);
+        if self.audience != expected {
+            return Err(Error::AudienceMismatch {
+                expected,
+                found: self.audience.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Bytes to sign to authorize the action at the audience
+    pub fn signable(&self, action: &SigType) -> [u8; 32] {
+        let action = action.as_str().as_bytes();
+        let audience = self.audience.as_bytes();
+
+        let mut material = Vec::with_capacity(32 + 16 + action.len() + audience.len());
+        material.extend_from_slice(&self.value);
+        material.extend_from_slice(&(action.len() as u64).to_be_bytes());
+        material.extend_from_slice(action);
+        material.extend_from_slice(&(audience.len() as u64).to_be_bytes());
+        material.extend_from_slice(audience);
+
+        crypto::blake3::derive_key(CONTEXT, &material)
+    }
+
+    pub fn verify(
+        &self,
+        signature: &crypto::Signature,
+        public_key: &crypto::PublicKey,
+        action: &SigType,
+    ) -> Result<(), Error> {
+        if self.expired() {
+            return Err(Error::Expired);
+        }
+
+        match public_key.verify(&self.signable(action), signature) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(Error::InvalidSignature),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static AUDIENCE: &str = "https://homeserver.example";
+
+    #[test]
+    fn test_challenge() {
+        let challenge = Challenge::create(now(), AUDIENCE, None);
+        let serialized = challenge.serialize();
+        let deserialized = Challenge::deserialize(&serialized).unwrap();
+
+        assert_eq!(serialized[0], CHALLENGE_VERSION);
+        assert_eq!(challenge, deserialized);
+        assert!(challenge.expires_at <= now())
+    }
+
+    #[test]
+    fn test_deserialize_malformed() {
+        let serialized = Challenge::create(now(), AUDIENCE, None).serialize();
+
+        assert!(matches!(
+            Challenge::deserialize(&[]),
+            Err(Error::InvalidLength(0))
+        ));
+        assert!(matches!(
+            Challenge::deserialize(&serialized[..40]),
+            Err(Error::InvalidLength(40))
+        ));
+        assert!(matches!(
+            Challenge::deserialize(&serialized[..serialized.len() - 1]),
+            Err(Error::InvalidLength(_))
+        ));
+        assert!(matches!(
+            Challenge::deserialize(&[serialized.as_slice(), &[0]].concat()),
+            Err(Error::InvalidLength(_))
+        ));
+
+        let mut invalid_audience = serialized.clone();
+        invalid_audience[HEADER_LEN] = 0xff;
+        assert!(matches!(
+            Challenge::deserialize(&invalid_audience),
+            Err(Error::InvalidAudience)
+        ));
+
+        let mut unsupported = serialized.clone();
+        unsupported[0] = 1;
+        assert!(matches!(
+            Challenge::deserialize(&unsupported),
+            Err(Error::UnsupportedVersion(1))
+        ));
+    }
+
+    #[test]
+    fn test_signable() {
+        let challenge = Challenge::create(now(), AUDIENCE, None);
+        let other = Challenge::new(
+            challenge.value,
+            challenge.expires_at,
+            "https://other.example",
+        );
+
+        assert_eq!(challenge.signable(&SigType::Login).len(), 32);
+        assert_ne!(
+            challenge.signable(&SigType::Login),
+            challenge.signable(&SigType::Signup)
+        );
+        assert_ne!(
+            challenge.signable(&SigType::Login),
+            other.signable(&SigType::Login)
+        );
+    }
+
+    #[test]
+    fn test_audience() {
+        let challenge = Challenge::create(now(), AUDIENCE, None);
+
+        assert!(challenge
+            .validate_audience(&Url::parse("https://homeserver.example/mvp").unwrap())
+            .is_ok());
+        assert!(matches!(
+            challenge.validate_audience(&Url::parse("https://other.example").unwrap()),
+            Err(Error::AudienceMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_expired() {
+        let challenge = Challenge::create(now() - 1000, AUDIENCE, None);
+
+        assert!(challenge.expired());
+    }
+
+    #[test]
+    fn test_validate() {
+        let policy = ChallengePolicy::default();
+
+        assert!(Challenge::create(now() + 60, AUDIENCE, None)
+            .validate(&policy)
+            .is_ok());
+        // Within the clock skew
+        assert!(Challenge::create(now() - 10, AUDIENCE, None)
+            .validate(&policy)
+            .is_ok());
+        assert!(Challenge::create(now() + 620, AUDIENCE, None)
+            .validate(&policy)
+            .is_ok());
+
+        assert!(matches!(
+            Challenge::create(now() - 60, AUDIENCE, None).validate(&policy),
+            Err(Error::Expired)
+        ));
+        assert!(matches!(
+            Challenge::create(now() + 3600, AUDIENCE, None).validate(&policy),
+            Err(Error::ExpiresTooLate(_))
+        ));
+
+        let strict = ChallengePolicy {
+            clock_skew: Duration::ZERO,
+            ..policy
+        };
+        assert!(matches!(
+            Challenge::create(now() - 10, AUDIENCE, None).validate(&strict),
+            Err(Error::Expired)
+        ));
+    }
+
+    #[test]
+    fn test_verify() {
+        let challenge = Challenge::create(now() + 1000, AUDIENCE, None);
+        let keypair = pkarr::Keypair::random();
+        let signature = keypair.sign(&challenge.signable(&SigType::Login));
+
+        assert!(challenge
+            .verify(&signature, &keypair.public_key(), &SigType::Login)
+            .is_ok());
+        assert!(matches!(
+            challenge.verify(&signature, &keypair.public_key(), &SigType::Signup),
+            Err(Error::InvalidSignature)
+        ));
+    }
+}
@@ -0,0 +1,335 @@
+use crate::error::ChallengeError as Error;
+use crate::transport::auth::SigType;
+use crate::transport::challenge::{audience, Challenge};
+use crate::transport::crypto::{PublicKey, Signature};
+use crate::transport::http::Url;
+use crate::utils::now;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Default lifetime of an issued challenge
+pub static DEFAULT_CHALLENGE_LIFETIME: Duration = Duration::from_secs(60);
+
+/// Storage of outstanding challenges, implement it to share them between homeserver instances
+pub trait ChallengeStorage {
+    /// Keeps the challenge until it is taken or pruned
+    fn insert(&self, challenge: Challenge) -> Result<(), Error>;
+
+    /// Returns the challenge with the value, leaving it in place
+    fn peek(&self, value: &[u8; 32]) -> Result<Option<Challenge>, Error>;
+
+    /// Removes and returns the challenge with the value, so it can be used only once
+    fn take(&self, value: &[u8; 32]) -> Result<Option<Challenge>, Error>;
+
+    /// Drops challenges which expired at `now`
+    fn prune(&self, now: u64) -> Result<(), Error>;
+}
+
+/// Keeps outstanding challenges in memory, clones share the same challenges
+#[derive(Debug, Clone, Default)]
+pub struct MemoryChallengeStorage {
+    challenges: Arc<Mutex<HashMap<[u8; 32], Challenge>>>,
+}
+
+impl MemoryChallengeStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of outstanding challenges
+    pub fn len(&self) -> usize {
+        self.challenges.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ChallengeStorage for MemoryChallengeStorage {
+    fn insert(&self, challenge: Challenge) -> Result<(), Error> {
+        self.challenges
+            .lock()
+            .unwrap()
+            .insert(challenge.value, challenge);
+        Ok(())
+    }
+
+    fn peek(&self, value: &[u8; 32]) -> Result<Option<Challenge>, Error> {
+        Ok(self.challenges.lock().unwrap().get(value).cloned())
+    }
+
+    fn take(&self, value: &[u8; 32]) -> Result<Option<Challenge>, Error> {
+        Ok(self.challenges.lock().unwrap().remove(value))
+    }
+
+    fn prune(&self, now: u64) -> Result<(), Error> {
+        self.challenges
+            .lock()
+            .unwrap()
+            .retain(|_, challenge| challenge.expires_at > now);
+        Ok(())
+    }
+}
+
+/// User authenticated by signing a challenge issued by the [`ChallengeStore`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub public_key: PublicKey,
+    /// Action the signature authorized
+    pub action: SigType,
+    /// Unix timestamp (seconds) of the verification
+    pub authenticated_at: u64,
+}
+
+impl Principal {
+    /// z-base-32 user id of the principal
+    pub fn user_id(&self) -> String {
+        self.public_key.to_z32()
+    }
+}
+
+/// Homeserver side of the challenge-response authentication.
+///
+/// Issues challenges, keeps the outstanding ones in a [`ChallengeStorage`] and verifies their
+/// signatures. A challenge is consumed by its first verification at its own homeserver,
+/// successful or not, so a signature can't be replayed. Challenges are bound to the homeserver URL
+/// the store was created for, so signatures collected by another homeserver are rejected, and
+/// challenges of other homeservers sharing the storage are left in place.
+pub struct ChallengeStore<S: ChallengeStorage = MemoryChallengeStorage> {
+    storage: S,
+    audience: String,
+    lifetime: Duration,
+}
+
+impl ChallengeStore {
+    /// Creates a store of the homeserver, keeping challenges in memory
+    pub fn new(homeserver_url: &Url) -> Self {
+        ChallengeStore::with_storage(homeserver_url, MemoryChallengeStorage::new())
+    }
+}
+
+impl<S: ChallengeStorage> ChallengeStore<S> {
+    pub fn with_storage(homeserver_url: &Url, storage: S) -> Self {
+        ChallengeStore {
+            storage,
+            audience: audience(homeserver_url),
+            lifetime: DEFAULT_CHALLENGE_LIFETIME,
+        }
+    }
+
+    /// Lifetime of the challenges issued from now on
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Issues a fresh challenge, dropping expired ones from the storage
+    pub fn issue(&self) -> Result<Challenge, Error> {
+        let now = now();
+        self.storage.prune(now)?;
+
+        let challenge = Challenge::create(now + self.lifetime.as_secs(), &self.audience, None);
+        self.storage.insert(challenge.clone())?;
+
+        Ok(challenge)
+    }
+
+    /// Verifies the signature of an issued challenge by the public key for the action, consuming
+    /// the challenge
+    pub fn verify(
+        &self,
+        value: &[u8; 32],
+        signature: &Signature,
+        public_key: &PublicKey,
+        action: &SigType,
+    ) -> Result<Principal, Error> {
+        // A challenge of another homeserver is only peeked at, so a probe here can't use it up
+        let challenge = self.storage.peek(value)?.ok_or(Error::UnknownChallenge)?;
+        if challenge.audience != self.audience {
+            return Err(Error::AudienceMismatch {
+                expected: self.audience.clone(),
+                found: challenge.audience,
+            });
+        }
+        // Whoever takes it first verifies it, concurrent verifications find it gone
+        let challenge = self.storage.take(value)?.ok_or(Error::UnknownChallenge)?;
+        challenge.verify(signature, public_key, action)?;
+
+        Ok(Principal {
+            public_key: public_key.clone(),
+            action: *action,
+            authenticated_at: now(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::crypto::{DeterministicKeyGen, Keypair};
+
+    fn homeserver_url() -> Url {
+        Url::parse("https://homeserver.example").unwrap()
+    }
+
+    #[test]
+    fn test_challenge_store() {
+        let keypair = Keypair::generate(Some(b"it is a seed for key generation!"));
+        let store = ChallengeStore::new(&homeserver_url());
+
+        let challenge = store.issue().unwrap();
+        assert_eq!(store.storage().len(), 1);
+
+        let signature = keypair.sign(&challenge.signable(&SigType::Login));
+        let principal = store
+            .verify(
+                &challenge.value,
+                &signature,
+                &keypair.public_key(),
+                &SigType::Login,
+            )
+            .unwrap();
+        assert_eq!(principal.action, SigType::Login);
+        assert_eq!(principal.user_id(), keypair.to_z32());
+        assert!(store.storage().is_empty());
+
+        // Replayed signature
+        assert!(matches!(
+            store.verify(
+                &challenge.value,
+                &signature,
+                &keypair.public_key(),
+                &SigType::Login
+            ),
+            Err(Error::UnknownChallenge)
+        ));
+
+        // Signature for another action
+        let challenge = store.issue().unwrap();
+        let signature = keypair.sign(&challenge.signable(&SigType::Signup));
+        assert!(matches!(
+            store.verify(
+                &challenge.value,
+                &signature,
+                &keypair.public_key(),
+                &SigType::Login
+            ),
+            Err(Error::InvalidSignature)
+        ));
+
+        // Challenge issued by another homeserver
+        let other = ChallengeStore::with_storage(
+            &Url::parse("https://other.example").unwrap(),
+            store.storage().clone(),
+        );
+        let challenge = other.issue().unwrap();
+        let signature = keypair.sign(&challenge.signable(&SigType::Login));
+        assert!(matches!(
+            store.verify(
+                &challenge.value,
+                &signature,
+                &keypair.public_key(),
+                &SigType::Login
+            ),
+            Err(Error::AudienceMismatch { .. })
+        ));
+        assert!(other
+            .verify(
+                &challenge.value,
+                &signature,
+                &keypair.public_key(),
+                &SigType::Login
+            )
+            .is_ok());
+
+        // Failed verification consumes the challenge too
+        let challenge = store.issue().unwrap();
+        let signature = Keypair::random().sign(&challenge.signable(&SigType::Login));
+        assert!(matches!(
+            store.verify(
+                &challenge.value,
+                &signature,
+                &keypair.public_key(),
+                &SigType::Login
+            ),
+            Err(Error::InvalidSignature)
+        ));
+        let signature = keypair.sign(&challenge.signable(&SigType::Login));
+        assert!(matches!(
+            store.verify(
+                &challenge.value,
+                &signature,
+                &keypair.public_key(),
+                &SigType::Login
+            ),
+            Err(Error::UnknownChallenge)
+        ));
+    }
+
+    #[test]
+    fn test_challenge_store_concurrent_verify() {
+        let keypair = Keypair::random();
+        let store = ChallengeStore::new(&homeserver_url());
+        let other = ChallengeStore::with_storage(
+            &Url::parse("https://other.example").unwrap(),
+            store.storage().clone(),
+        );
+
+        let challenge = store.issue().unwrap();
+        let signature = keypair.sign(&challenge.signable(&SigType::Login));
+        let verify = |store: &ChallengeStore| {
+            store.verify(
+                &challenge.value,
+                &signature,
+                &keypair.public_key(),
+                &SigType::Login,
+            )
+        };
+
+        // Probes of the other homeserver never take the challenge from under its own one
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let store = if i % 2 == 0 { &store } else { &other };
+                    scope.spawn(move || verify(store))
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+        assert!(results.iter().all(|r| matches!(
+            r,
+            Ok(_) | Err(Error::UnknownChallenge) | Err(Error::AudienceMismatch { .. })
+        )));
+        assert!(store.storage().is_empty());
+    }
+
+    #[test]
+    fn test_challenge_store_expired() {
+        let keypair = Keypair::random();
+        let store = ChallengeStore::new(&homeserver_url()).lifetime(Duration::ZERO);
+
+        let challenge = store.issue().unwrap();
+        let signature = keypair.sign(&challenge.signable(&SigType::Login));
+        assert!(matches!(
+            store.verify(
+                &challenge.value,
+                &signature,
+                &keypair.public_key(),
+                &SigType::Login
+            ),
+            Err(Error::Expired)
+        ));
+
+        // Expired challenges are dropped when new ones are issued
+        store.issue().unwrap();
+        store.issue().unwrap();
+        assert_eq!(store.storage().len(), 1);
+    }
+}
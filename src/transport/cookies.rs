@@ -0,0 +1,233 @@
+use crate::transport::http::Url;
+use crate::utils::now;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Name of the cookie carrying the session at the homeserver
+pub(crate) static SESSION_COOKIE: &str = "sessionId";
+
+/// Session cookie issued by a homeserver to a user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionCookie {
+    /// Origin of the homeserver, e.g. `https://homeserver.example.com`
+    pub homeserver: String,
+    pub user_id: String,
+    pub value: String,
+    /// Only sent to URLs under this path, defaults to the whole homeserver (`/`): sessions set by
+    /// signup or login are used for every other endpoint
+    pub path: String,
+    /// Only sent over `https`
+    pub secure: bool,
+    /// Unix timestamp (seconds), `None` for cookies living as long as the client
+    pub expires_at: Option<u64>,
+}
+
+impl SessionCookie {
+    /// Builds the session cookie from a `Set-Cookie` of a response to `url`
+    pub(crate) fn from_response(
+        url: &Url,
+        user_id: &str,
+        cookie: &reqwest::cookie::Cookie,
+    ) -> SessionCookie {
+        // Max-Age takes precedence over Expires (RFC 6265, section 5.3)
+        let expires_at = match cookie.max_age() {
+            Some(max_age) => Some(now().saturating_add(max_age.as_secs())),
+            None => cookie.expires().map(|expires| {
+                expires
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0)
+            }),
+        };
+
+        SessionCookie {
+            homeserver: origin(url),
+            user_id: user_id.to_string(),
+            value: cookie.value().to_string(),
+            path: cookie
+                .path()
+                .filter(|path| path.starts_with('/'))
+                .unwrap_or("/")
+                .to_string(),
+            secure: cookie.secure(),
+            expires_at,
+        }
+    }
+
+    /// Checks if the cookie is past its expiry
+    pub fn expired(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => expires_at <= now(),
+            None => false,
+        }
+    }
+
+    /// Checks if the cookie should be sent along with a request to the url
+    pub fn matches(&self, url: &Url) -> bool {
+        if self.expired() || self.homeserver != origin(url) {
+            return false;
+        }
+        if self.secure && url.scheme() != "https" {
+            return false;
+        }
+
+        let path = url.path();
+        path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')))
+    }
+}
+
+/// Sessions of any number of users at any number of homeservers.
+///
+/// Sessions are keyed by homeserver origin and user id, so a single client can stay logged in
+/// to several accounts at once. Clones share the same sessions.
+#[derive(Debug, Clone, Default)]
+pub struct SessionStore {
+    sessions: Arc<Mutex<HashMap<(String, String), SessionCookie>>>,
+}
+
+impl SessionStore {
+    pub fn new() -> SessionStore {
+        SessionStore::default()
+    }
+
+    /// Returns the live session of the user at the homeserver
+    pub fn get(&self, homeserver_url: &Url, user_id: &str) -> Option<SessionCookie> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let key = (origin(homeserver_url), user_id.to_string());
+
+        match sessions.get(&key) {
+            Some(cookie) if cookie.expired() => {
+                sessions.remove(&key);
+                None
+            }
+            cookie => cookie.cloned(),
+        }
+    }
+
+    /// Stores the session, replacing the previous one of the same user at the same homeserver
+    pub fn insert(&self, cookie: SessionCookie) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert((cookie.homeserver.clone(), cookie.user_id.clone()), cookie);
+    }
+
+    /// Forgets the session of the user at the homeserver, returning it if there was one
+    pub fn remove(&self, homeserver_url: &Url, user_id: &str) -> Option<SessionCookie> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .remove(&(origin(homeserver_url), user_id.to_string()))
+    }
+
+    /// Lists all live sessions, dropping the expired ones
+    pub fn list(&self) -> Vec<SessionCookie> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, cookie| !cookie.expired());
+
+        let mut list: Vec<SessionCookie> = sessions.values().cloned().collect();
+        list.sort_by(|a, b| (&a.homeserver, &a.user_id).cmp(&(&b.homeserver, &b.user_id)));
+        list
+    }
+
+    /// Returns the `Cookie` header value to send on behalf of the user, if any
+    pub(crate) fn cookie_header(&self, url: &Url, user_id: &str) -> Option<String> {
+        self.get(url, user_id)
+            .filter(|cookie| cookie.matches(url))
+            .map(|cookie| format!("{}={}", SESSION_COOKIE, cookie.value))
+    }
+
+    /// Updates the session of the user from the cookies set by the response to `url`
+    pub(crate) fn store_response_cookies<'c>(
+        &self,
+        url: &Url,
+        user_id: &str,
+        cookies: impl Iterator<Item = reqwest::cookie::Cookie<'c>>,
+    ) {
+        for cookie in cookies.filter(|c| c.name() == SESSION_COOKIE) {
+            let cookie = SessionCookie::from_response(url, user_id, &cookie);
+
+            // A past expiry is how a server deletes a cookie
+            if cookie.expired() {
+                self.remove(url, user_id);
+            } else {
+                self.insert(cookie);
+            }
+        }
+    }
+}
+
+/// Serialized origin of the url, e.g. `http://localhost:6287`
+fn origin(url: &Url) -> String {
+    url.origin().ascii_serialization()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookie(homeserver: &str, user_id: &str, path: &str) -> SessionCookie {
+        SessionCookie {
+            homeserver: homeserver.to_string(),
+            user_id: user_id.to_string(),
+            value: format!("{}-session", user_id),
+            path: path.to_string(),
+            secure: false,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_session_store() {
+        let store = SessionStore::new();
+        let homeserver = Url::parse("http://localhost:6287").unwrap();
+        let other = Url::parse("https://other.example.com").unwrap();
+
+        store.insert(cookie("http://localhost:6287", "alice", "/"));
+        store.insert(cookie("http://localhost:6287", "bob", "/"));
+        store.insert(cookie("https://other.example.com", "alice", "/"));
+
+        assert_eq!(store.list().len(), 3);
+        assert_eq!(
+            store.get(&homeserver, "alice").unwrap().value,
+            "alice-session"
+        );
+        assert_eq!(
+            store
+                .cookie_header(&homeserver.join("/mvp/session").unwrap(), "bob")
+                .unwrap(),
+            "sessionId=bob-session"
+        );
+
+        assert!(store.remove(&other, "alice").is_some());
+        assert!(store.get(&other, "alice").is_none());
+        assert!(store.get(&homeserver, "alice").is_some());
+        assert_eq!(store.list().len(), 2);
+    }
+
+    #[test]
+    fn test_session_cookie_attributes() {
+        let url = Url::parse("http://localhost:6287/mvp/session").unwrap();
+
+        let mut scoped = cookie("http://localhost:6287", "alice", "/mvp");
+        assert!(scoped.matches(&url));
+        assert!(!scoped.matches(&Url::parse("http://localhost:6287/mvpx").unwrap()));
+        assert!(!scoped.matches(&Url::parse("http://localhost:6287/other").unwrap()));
+        assert!(!scoped.matches(&Url::parse("http://localhost:6288/mvp").unwrap()));
+
+        scoped.secure = true;
+        assert!(!scoped.matches(&url));
+
+        let mut expired = cookie("http://localhost:6287", "bob", "/");
+        expired.expires_at = Some(now() - 1);
+        assert!(!expired.matches(&url));
+
+        let store = SessionStore::new();
+        store.insert(expired);
+        assert!(store.get(&url, "bob").is_none());
+        assert!(store.list().is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::error::EncryptionError as Error;
+use crate::transport::crypto::{blake3, random_bytes};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+/// Version of the envelope, the first byte of every encrypted payload
+pub static ENVELOPE_VERSION: u8 = 1;
+
+/// Context of the repository encryption key, kept apart from child key derivation so no
+/// [`crate::transport::crypto::derive_seed`] label yields it
+static ENCRYPTION_KEY_CONTEXT: &str = "pubky 2024-04 repository encryption key v1";
+
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+
+/// Derives the repository encryption key from the root seed
+pub fn derive_key(seed: &[u8; 32]) -> [u8; 32] {
+    blake3::derive_key(ENCRYPTION_KEY_CONTEXT, seed)
+}
+
+/// Encrypts the plaintext with XChaCha20-Poly1305 into `version || nonce || ciphertext`.
+///
+/// Nonces are random, 24 bytes are enough to never repeat one under the same key. `aad` is
+/// authenticated but not stored, the same value has to be passed to [`decrypt`].
+pub fn encrypt(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+    let nonce = random_bytes(NONCE_LEN);
+
+    let ciphertext = XChaCha20Poly1305::new(key.into())
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: &envelope_aad(aad),
+            },
+        )
+        .expect("Failed to encrypt payload");
+
+    let mut envelope = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+    envelope.push(ENVELOPE_VERSION);
+    envelope.extend_from_slice(&nonce);
+    envelope.extend_from_slice(&ciphertext);
+    envelope
+}
+
+/// Decrypts an envelope produced by [`encrypt`], fails if it was tampered with
+pub fn decrypt(key: &[u8; 32], envelope: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+    match envelope.first() {
+        None => return Err(Error::InvalidEnvelope("empty payload".to_string())),
+        Some(version) if *version != ENVELOPE_VERSION => {
+            return Err(Error::UnsupportedVersion(*version))
+        }
+        Some(_) => (),
+    }
+    if envelope.len() < 1 + NONCE_LEN + TAG_LEN {
+        return Err(Error::InvalidEnvelope("payload too short".to_string()));
+    }
+
+    let (nonce, ciphertext) = envelope[1..].split_at(NONCE_LEN);
+
+    XChaCha20Poly1305::new(key.into())
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &envelope_aad(aad),
+            },
+        )
+        .map_err(|_| Error::FailedToDecrypt)
+}
+
+/// Location of a record, so ciphertext moved to another path fails to decrypt
+pub fn record_aad(user_id: &str, repo_name: &str, path: &str) -> Vec<u8> {
+    format!("{}/{}/{}", user_id, repo_name, path).into_bytes()
+}
+
+// The version is authenticated too, so it can't be swapped for a weaker future format
+fn envelope_aad(aad: &[u8]) -> Vec<u8> {
+    let mut envelope_aad = vec![ENVELOPE_VERSION];
+    envelope_aad.extend_from_slice(aad);
+    envelope_aad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::crypto::derive_seed;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = b"it is a key for the encryption!!";
+        let aad = record_aad("user", "repo", "path");
+
+        let envelope = encrypt(key, b"secret", &aad);
+        assert_eq!(envelope[0], ENVELOPE_VERSION);
+        assert_eq!(envelope.len(), 1 + NONCE_LEN + 6 + TAG_LEN);
+        assert_ne!(envelope, encrypt(key, b"secret", &aad));
+        assert_eq!(decrypt(key, &envelope, &aad).unwrap(), b"secret");
+
+        let other_aad = record_aad("user", "repo", "other_path");
+        assert!(matches!(
+            decrypt(key, &envelope, &other_aad),
+            Err(Error::FailedToDecrypt)
+        ));
+        assert!(matches!(
+            decrypt(b"it is another key for encryption", &envelope, &aad),
+            Err(Error::FailedToDecrypt)
+        ));
+
+        let mut tampered = envelope.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            decrypt(key, &tampered, &aad),
+            Err(Error::FailedToDecrypt)
+        ));
+
+        tampered[0] = 2;
+        assert!(matches!(
+            decrypt(key, &tampered, &aad),
+            Err(Error::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            decrypt(key, &envelope[..20], &aad),
+            Err(Error::InvalidEnvelope(_))
+        ));
+    }
+
+    #[test]
+    fn test_derive_key() {
+        let seed = b"it is a seed for key generation!";
+        let key = derive_key(seed);
+
+        assert_eq!(key, derive_key(seed));
+        assert_ne!(key, derive_key(b"not a seed for a key generation!"));
+        // Not reachable through child key derivation
+        assert_ne!(key, derive_seed(seed, "pdk:repository-encryption/v1"));
+        assert_ne!(key, derive_seed(seed, ""));
+    }
+}
@@ -0,0 +1,323 @@
+use crate::error::KeystoreError as Error;
+use crate::transport::crypto::{random_bytes, zeroize, DeterministicKeyGen, Keypair, PublicKey};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+/// Identifies the keystore file
+pub static MAGIC: &[u8; 4] = b"pdks";
+pub static KEYSTORE_VERSION: u8 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const SEED_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+// magic, version, 3 KDF params, salt, nonce, public key
+const HEADER_LEN: usize = 4 + 1 + 3 * 4 + SALT_LEN + NONCE_LEN + 32;
+const KEYSTORE_LEN: usize = HEADER_LEN + SEED_LEN + TAG_LEN;
+
+/// Parameters of the Argon2id key derivation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// Upper bounds of the parameters, a keystore read from disk is not trusted to set its own
+    /// cost before the passphrase is checked
+    pub const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+    pub const MAX_ITERATIONS: u32 = 64;
+    pub const MAX_PARALLELISM: u32 = 16;
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.memory_kib > Self::MAX_MEMORY_KIB
+            || self.iterations > Self::MAX_ITERATIONS
+            || self.parallelism > Self::MAX_PARALLELISM
+        {
+            return Err(Error::InvalidParams(format!(
+                "{:?} exceed the maximum memory of {} KiB, {} iterations or parallelism of {}",
+                self,
+                Self::MAX_MEMORY_KIB,
+                Self::MAX_ITERATIONS,
+                Self::MAX_PARALLELISM
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Seed encrypted at rest under a passphrase.
+///
+/// The seed is encrypted with XChaCha20-Poly1305 under a key derived from the passphrase with
+/// Argon2id. The header (format version, KDF parameters, salt, nonce and public key) is stored in
+/// clear and authenticated along with the seed, so the identity can be shown without unlocking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keystore {
+    params: KdfParams,
+    salt: [u8; SALT_LEN],
+    nonce: [u8; NONCE_LEN],
+    public_key: PublicKey,
+    ciphertext: Vec<u8>,
+}
+
+impl Keystore {
+    /// Encrypts the seed under the passphrase with default KDF parameters
+    pub fn create(seed: &[u8; 32], passphrase: &str) -> Result<Keystore, Error> {
+        Keystore::create_with_params(seed, passphrase, KdfParams::default())
+    }
+
+    /// Encrypts the seed under the passphrase
+    pub fn create_with_params(
+        seed: &[u8; 32],
+        passphrase: &str,
+        params: KdfParams,
+    ) -> Result<Keystore, Error> {
+        params.validate()?;
+        let mut keystore = Keystore {
+            params,
+            salt: random_bytes(SALT_LEN).try_into().unwrap(),
+            nonce: random_bytes(NONCE_LEN).try_into().unwrap(),
+            public_key: Keypair::generate(Some(seed)).public_key(),
+            ciphertext: vec![],
+        };
+
+        let mut key = keystore.derive_key(passphrase)?;
+        let ciphertext = XChaCha20Poly1305::new(&key.into()).encrypt(
+            XNonce::from_slice(&keystore.nonce),
+            Payload {
+                msg: seed,
+                aad: &keystore.header(),
+            },
+        );
+        zeroize(&mut key);
+
+        keystore.ciphertext = ciphertext.map_err(|_| Error::FailedToEncrypt)?;
+        Ok(keystore)
+    }
+
+    /// Decrypts the seed, fails on a wrong passphrase or a tampered keystore
+    pub fn unlock(&self, passphrase: &str) -> Result<[u8; 32], Error> {
+        let mut key = self.derive_key(passphrase)?;
+        let plaintext = XChaCha20Poly1305::new(&key.into()).decrypt(
+            XNonce::from_slice(&self.nonce),
+            Payload {
+                msg: &self.ciphertext,
+                aad: &self.header(),
+            },
+        );
+        zeroize(&mut key);
+
+        let mut plaintext = plaintext.map_err(|_| Error::InvalidPassphrase)?;
+        let seed: Result<[u8; 32], _> = plaintext.as_slice().try_into();
+        zeroize(&mut plaintext);
+        let seed = seed.map_err(|_| Error::InvalidFormat("invalid seed length".to_string()))?;
+
+        if Keypair::generate(Some(&seed)).public_key() != self.public_key {
+            return Err(Error::PublicKeyMismatch);
+        }
+
+        Ok(seed)
+    }
+
+    /// Re-encrypts the seed under a new passphrase, with fresh salt and nonce
+    pub fn change_passphrase(
+        &mut self,
+        passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<(), Error> {
+        let mut seed = self.unlock(passphrase)?;
+        let keystore = Keystore::create_with_params(&seed, new_passphrase, self.params);
+        zeroize(&mut seed);
+
+        *self = keystore?;
+        Ok(())
+    }
+
+    /// Public key of the stored identity, available without the passphrase
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    pub fn params(&self) -> KdfParams {
+        self.params
+    }
+
+    /// Serializes the keystore as `header || ciphertext`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header();
+        bytes.extend_from_slice(&self.ciphertext);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Keystore, Error> {
+        if bytes.len() < MAGIC.len() + 1 || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(Error::InvalidFormat("not a keystore".to_string()));
+        }
+        if bytes[MAGIC.len()] != KEYSTORE_VERSION {
+            return Err(Error::UnsupportedVersion(bytes[MAGIC.len()]));
+        }
+        if bytes.len() != KEYSTORE_LEN {
+            return Err(Error::InvalidFormat(format!(
+                "expected {} bytes, got {}",
+                KEYSTORE_LEN,
+                bytes.len()
+            )));
+        }
+
+        let u32_at = |i: usize| u32::from_be_bytes(bytes[i..i + 4].try_into().unwrap());
+        let params = KdfParams {
+            memory_kib: u32_at(5),
+            iterations: u32_at(9),
+            parallelism: u32_at(13),
+        };
+        params.validate()?;
+
+        let mut offset = 17;
+        let salt = bytes[offset..offset + SALT_LEN].try_into().unwrap();
+        offset += SALT_LEN;
+        let nonce = bytes[offset..offset + NONCE_LEN].try_into().unwrap();
+        offset += NONCE_LEN;
+        let public_key: &[u8; 32] = bytes[offset..offset + 32].try_into().unwrap();
+        let public_key = PublicKey::try_from(*public_key)
+            .map_err(|_| Error::InvalidFormat("invalid public key".to_string()))?;
+
+        Ok(Keystore {
+            params,
+            salt,
+            nonce,
+            public_key,
+            ciphertext: bytes[HEADER_LEN..].to_vec(),
+        })
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.push(KEYSTORE_VERSION);
+        header.extend_from_slice(&self.params.memory_kib.to_be_bytes());
+        header.extend_from_slice(&self.params.iterations.to_be_bytes());
+        header.extend_from_slice(&self.params.parallelism.to_be_bytes());
+        header.extend_from_slice(&self.salt);
+        header.extend_from_slice(&self.nonce);
+        header.extend_from_slice(self.public_key.as_bytes());
+        header
+    }
+
+    fn derive_key(&self, passphrase: &str) -> Result<[u8; 32], Error> {
+        let params = Params::new(
+            self.params.memory_kib,
+            self.params.iterations,
+            self.params.parallelism,
+            Some(32),
+        )
+        .map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &self.salt, &mut key)
+            .map_err(|e| Error::InvalidParams(e.to_string()))?;
+
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap parameters, so tests don't spend seconds in the KDF
+    static TEST_PARAMS: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_keystore() {
+        let seed = b"it is a seed for key generation!";
+        let mut keystore = Keystore::create_with_params(seed, "passphrase", TEST_PARAMS).unwrap();
+
+        assert_eq!(
+            keystore.public_key(),
+            &Keypair::generate(Some(seed)).public_key()
+        );
+        assert_eq!(&keystore.unlock("passphrase").unwrap(), seed);
+        assert!(matches!(
+            keystore.unlock("wrong"),
+            Err(Error::InvalidPassphrase)
+        ));
+
+        let bytes = keystore.to_bytes();
+        assert_eq!(bytes.len(), KEYSTORE_LEN);
+        assert_eq!(Keystore::from_bytes(&bytes).unwrap(), keystore);
+
+        keystore
+            .change_passphrase("passphrase", "new passphrase")
+            .unwrap();
+        assert!(keystore.unlock("passphrase").is_err());
+        assert_eq!(&keystore.unlock("new passphrase").unwrap(), seed);
+        assert_ne!(keystore.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_keystore_tampered() {
+        let seed = b"it is a seed for key generation!";
+        let bytes = Keystore::create_with_params(seed, "passphrase", TEST_PARAMS)
+            .unwrap()
+            .to_bytes();
+
+        // Swapped public key is caught by the authenticated header
+        let mut tampered = bytes.clone();
+        let other = Keypair::random().public_key();
+        tampered[HEADER_LEN - 32..HEADER_LEN].copy_from_slice(other.as_bytes());
+        let keystore = Keystore::from_bytes(&tampered).unwrap();
+        assert!(matches!(
+            keystore.unlock("passphrase"),
+            Err(Error::InvalidPassphrase)
+        ));
+
+        let mut tampered = bytes.clone();
+        tampered[4] = 2;
+        assert!(matches!(
+            Keystore::from_bytes(&tampered),
+            Err(Error::UnsupportedVersion(2))
+        ));
+
+        // Unbounded KDF cost is rejected before unlocking
+        let mut tampered = bytes.clone();
+        tampered[5..9].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            Keystore::from_bytes(&tampered),
+            Err(Error::InvalidParams(_))
+        ));
+        let mut tampered = bytes.clone();
+        tampered[9..13].copy_from_slice(&(KdfParams::MAX_ITERATIONS + 1).to_be_bytes());
+        assert!(matches!(
+            Keystore::from_bytes(&tampered),
+            Err(Error::InvalidParams(_))
+        ));
+
+        assert!(matches!(
+            Keystore::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            Keystore::from_bytes(b"not a keystore"),
+            Err(Error::InvalidFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::error::MnemonicError as Error;
+use bip39::{Language, Mnemonic};
+
+/// Number of words encoding a 32-byte seed
+pub const WORD_COUNT: usize = 24;
+
+/// Encodes the seed as a BIP-39 recovery phrase of 24 English words.
+///
+/// The words encode the seed itself, not a BIP-39 derived seed, so the phrase gives back the same
+/// `Keypair` as the seed passed to `DeterministicKeyGen::generate`.
+pub fn seed_to_mnemonic(seed: &[u8; 32]) -> String {
+    Mnemonic::from_entropy_in(Language::English, seed)
+        .expect("32 bytes is a valid entropy length")
+        .to_string()
+}
+
+/// Decodes the seed from a recovery phrase, ignoring case and extra whitespace
+pub fn mnemonic_to_seed(phrase: &str) -> Result<[u8; 32], Error> {
+    let words: Vec<String> = phrase
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    if words.len() != WORD_COUNT {
+        return Err(Error::InvalidWordCount(words.len()));
+    }
+
+    let mnemonic = Mnemonic::parse_in_normalized(Language::English, &words.join(" ")).map_err(
+        |e| match e {
+            bip39::Error::UnknownWord(i) => Error::UnknownWord(words[i].clone()),
+            bip39::Error::InvalidChecksum => Error::InvalidChecksum,
+            bip39::Error::BadWordCount(count) => Error::InvalidWordCount(count),
+            e => Error::InvalidPhrase(e.to_string()),
+        },
+    )?;
+
+    let (entropy, len) = mnemonic.to_entropy_array();
+    entropy[..len]
+        .try_into()
+        .map_err(|_| Error::InvalidWordCount(words.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::crypto::{DeterministicKeyGen, Keypair};
+
+    #[test]
+    fn test_mnemonic_round_trip() {
+        let seed = b"it is a seed for key generation!";
+        let phrase = seed_to_mnemonic(seed);
+        assert_eq!(phrase.split(' ').count(), WORD_COUNT);
+
+        let decoded = mnemonic_to_seed(&format!("  {}\n", phrase.to_uppercase())).unwrap();
+        assert_eq!(&decoded, seed);
+        assert_eq!(
+            Keypair::generate(Some(&decoded)).to_z32(),
+            Keypair::generate(Some(seed)).to_z32()
+        );
+
+        // Known vector: zero entropy
+        let zero = seed_to_mnemonic(&[0; 32]);
+        assert!(zero.starts_with("abandon abandon"));
+        assert!(zero.ends_with("abandon art"));
+    }
+
+    #[test]
+    fn test_mnemonic_errors() {
+        let phrase = seed_to_mnemonic(b"it is a seed for key generation!");
+        let mut words: Vec<&str> = phrase.split(' ').collect();
+
+        assert!(matches!(
+            mnemonic_to_seed(&words[..12].join(" ")),
+            Err(Error::InvalidWordCount(12))
+        ));
+
+        words[3] = "pubky";
+        assert!(matches!(
+            mnemonic_to_seed(&words.join(" ")),
+            Err(Error::UnknownWord(word)) if word == "pubky"
+        ));
+
+        words[3] = if words[0] == "zoo" { "abandon" } else { "zoo" };
+        words.swap(0, 3);
+        assert!(matches!(
+            mnemonic_to_seed(&words.join(" ")),
+            Err(Error::InvalidChecksum)
+        ));
+    }
+}
@@ -0,0 +1,143 @@
+pub mod encryption;
+pub mod keystore;
+pub mod mnemonic;
+pub mod sharing;
+pub mod signed;
+
+// Re-exporting the blake3 crate
+#[allow(unused_imports)]
+pub use blake3;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+// Re-exporting public key for the challenge
+#[allow(unused_imports)]
+pub use pkarr::{Keypair, PublicKey};
+
+use ed25519_dalek::SigningKey;
+
+// Re-exporting public key for the challenge
+pub use ed25519_dalek::Signature;
+
+pub trait DeterministicKeyGen {
+    fn generate(seed: Option<&[u8; 32]>) -> Self;
+}
+
+impl DeterministicKeyGen for Keypair {
+    fn generate(seed: Option<&[u8; 32]>) -> Self {
+        match seed {
+            Some(seed) => {
+                let signing_key = SigningKey::from_bytes(seed);
+                Keypair::from_secret_key(&signing_key.to_bytes())
+            }
+            None => Keypair::random(),
+        }
+    }
+}
+
+/// Context of child key derivation
+static CHILD_KEY_CONTEXT: &str = "pubky 2024-04 child key derivation";
+
+/// Derives the seed of a child key from the root seed and a label, e.g. `app:chat/v1`.
+///
+/// Derivation is one-way, a child seed reveals neither the root seed nor other children. Bumping
+/// the version in the label replaces a compromised child key without touching the root identity.
+pub fn derive_seed(seed: &[u8; 32], label: &str) -> [u8; 32] {
+    derive_in_context(CHILD_KEY_CONTEXT, seed, label)
+}
+
+/// Derives a key from the seed and a label under a fixed context, the label is length-prefixed
+/// so it cannot run into the seed
+pub(crate) fn derive_in_context(context: &'static str, seed: &[u8; 32], label: &str) -> [u8; 32] {
+    let mut material = Vec::with_capacity(8 + label.len() + seed.len());
+    material.extend_from_slice(&(label.len() as u64).to_be_bytes());
+    material.extend_from_slice(label.as_bytes());
+    material.extend_from_slice(seed);
+
+    let key = blake3::derive_key(context, &material);
+    zeroize(&mut material);
+
+    key
+}
+
+/// Derives a child keypair from the root seed, see [`derive_seed`]
+pub fn derive_keypair(seed: &[u8; 32], label: &str) -> Keypair {
+    let mut child_seed = derive_seed(seed, label);
+    let keypair = Keypair::generate(Some(&child_seed));
+    zeroize(&mut child_seed);
+
+    keypair
+}
+
+pub fn zeroize(buf: &mut [u8]) {
+    for byte in buf.iter_mut() {
+        *byte = 0;
+    }
+}
+
+pub fn random_bytes(len: usize) -> Vec<u8> {
+    let mut rng = OsRng;
+    let mut buffer = vec![0u8; len];
+    rng.fill_bytes(&mut buffer);
+
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zeroize() {
+        let mut buf = vec![1, 2, 3, 4, 5];
+        zeroize(&mut buf);
+        assert_eq!(buf, vec![0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_deterministic_keygen() {
+        let seed_1 = b"it is a seed for key generation!";
+        let seed_2 = b"not a seed for a key generation!";
+
+        assert_eq!(
+            Keypair::generate(Some(seed_1)).to_z32(),
+            Keypair::generate(Some(seed_1)).to_z32()
+        );
+
+        assert_ne!(
+            Keypair::generate(Some(seed_1)).to_z32(),
+            Keypair::generate(Some(seed_2)).to_z32()
+        );
+    }
+
+    #[test]
+    fn test_derive_keypair() {
+        let seed = b"it is a seed for key generation!";
+        let root = Keypair::generate(Some(seed));
+        let chat = derive_keypair(seed, "app:chat/v1");
+
+        assert_eq!(chat.to_z32(), derive_keypair(seed, "app:chat/v1").to_z32());
+        assert_ne!(chat.to_z32(), root.to_z32());
+        assert_ne!(chat.to_z32(), derive_keypair(seed, "app:chat/v2").to_z32());
+        assert_ne!(
+            chat.to_z32(),
+            derive_keypair(b"not a seed for a key generation!", "app:chat/v1").to_z32()
+        );
+        assert_eq!(
+            chat.to_z32(),
+            Keypair::generate(Some(&derive_seed(seed, "app:chat/v1"))).to_z32()
+        );
+    }
+
+    #[test]
+    fn test_random_bytes() {
+        let len = 32;
+        let bytes_1 = random_bytes(len);
+        let bytes_2 = random_bytes(len);
+
+        assert_eq!(bytes_1.len(), len);
+        assert_eq!(bytes_2.len(), len);
+        assert_ne!(bytes_1, bytes_2);
+    }
+}
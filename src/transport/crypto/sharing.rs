@@ -0,0 +1,213 @@
+use crate::error::EncryptionError as Error;
+use crate::transport::crypto::encryption::{decrypt, encrypt};
+use crate::transport::crypto::{random_bytes, zeroize, Keypair, PublicKey};
+use ed25519_dalek::SigningKey;
+use x25519_dalek::{PublicKey as X25519PublicKey, StaticSecret};
+
+/// Version of the multi-recipient envelope
+pub static SHARE_VERSION: u8 = 1;
+
+/// Context of the key wrapping the content key for a single recipient
+static WRAP_KEY_CONTEXT: &str = "pubky 2024-04 shared record key wrap";
+
+// version, ephemeral public key, recipient count
+const HEADER_LEN: usize = 1 + 32 + 2;
+// encrypted 32-byte content key: version, nonce, key, tag
+const WRAPPED_KEY_LEN: usize = 1 + 24 + 32 + 16;
+const RECIPIENT_LEN: usize = 32 + WRAPPED_KEY_LEN;
+
+/// Encrypts the plaintext for every recipient.
+///
+/// The plaintext is encrypted once under a random content key, which is then wrapped for each
+/// recipient with a key agreed between an ephemeral X25519 key and the recipient's pubky key
+/// converted to X25519. The envelope is
+/// `version || ephemeral key || count || (recipient || wrapped key)* || encrypted content`,
+/// and the recipient list is authenticated along with `aad`.
+pub fn seal(plaintext: &[u8], recipients: &[PublicKey], aad: &[u8]) -> Result<Vec<u8>, Error> {
+    if recipients.is_empty() {
+        return Err(Error::NoRecipients);
+    }
+    let count = u16::try_from(recipients.len())
+        .map_err(|_| Error::InvalidEnvelope("too many recipients".to_string()))?;
+
+    let mut content_key: [u8; 32] = random_bytes(32).try_into().unwrap();
+    let ephemeral = StaticSecret::from(<[u8; 32]>::try_from(random_bytes(32)).unwrap());
+    let ephemeral_public = X25519PublicKey::from(&ephemeral);
+
+    let mut envelope = Vec::with_capacity(HEADER_LEN + recipients.len() * RECIPIENT_LEN);
+    envelope.push(SHARE_VERSION);
+    envelope.extend_from_slice(ephemeral_public.as_bytes());
+    envelope.extend_from_slice(&count.to_be_bytes());
+
+    for recipient in recipients {
+        let shared = ephemeral.diffie_hellman(&x25519_public_key(recipient));
+        let mut wrap_key = wrap_key(shared.as_bytes(), ephemeral_public.as_bytes(), recipient);
+
+        envelope.extend_from_slice(recipient.as_bytes());
+        envelope.extend_from_slice(&encrypt(&wrap_key, &content_key, &[]));
+        zeroize(&mut wrap_key);
+    }
+
+    let content = encrypt(&content_key, plaintext, &content_aad(&envelope, aad));
+    zeroize(&mut content_key);
+
+    envelope.extend_from_slice(&content);
+    Ok(envelope)
+}
+
+/// Decrypts an envelope produced by [`seal`] with the keypair of one of its recipients
+pub fn open(envelope: &[u8], keypair: &Keypair, aad: &[u8]) -> Result<Vec<u8>, Error> {
+    let content_offset = content_offset(envelope)?;
+    let ephemeral_public: [u8; 32] = envelope[1..33].try_into().unwrap();
+
+    let public_key = keypair.public_key();
+    let wrapped = envelope[HEADER_LEN..content_offset]
+        .chunks(RECIPIENT_LEN)
+        .find(|recipient| &recipient[..32] == public_key.as_bytes())
+        .map(|recipient| &recipient[32..])
+        .ok_or(Error::NotARecipient)?;
+
+    let secret = x25519_secret(keypair);
+    let shared = secret.diffie_hellman(&X25519PublicKey::from(ephemeral_public));
+    let mut wrap_key = wrap_key(shared.as_bytes(), &ephemeral_public, &public_key);
+    let content_key = decrypt(&wrap_key, wrapped, &[]);
+    zeroize(&mut wrap_key);
+
+    let mut content_key: [u8; 32] = content_key?
+        .try_into()
+        .map_err(|_| Error::InvalidEnvelope("invalid content key".to_string()))?;
+    let plaintext = decrypt(
+        &content_key,
+        &envelope[content_offset..],
+        &content_aad(&envelope[..content_offset], aad),
+    );
+    zeroize(&mut content_key);
+
+    plaintext
+}
+
+/// Lists the public keys the envelope was sealed for
+pub fn recipients(envelope: &[u8]) -> Result<Vec<PublicKey>, Error> {
+    let content_offset = content_offset(envelope)?;
+
+    envelope[HEADER_LEN..content_offset]
+        .chunks(RECIPIENT_LEN)
+        .map(|recipient| {
+            let bytes: [u8; 32] = recipient[..32].try_into().unwrap();
+            PublicKey::try_from(bytes).map_err(|e| Error::InvalidRecipient(e.to_string()))
+        })
+        .collect()
+}
+
+/// Checks the version and the recipient list of the envelope, returns where the encrypted content
+/// starts
+fn content_offset(envelope: &[u8]) -> Result<usize, Error> {
+    match envelope.first() {
+        None => return Err(Error::InvalidEnvelope("empty payload".to_string())),
+        Some(version) if *version != SHARE_VERSION => {
+            return Err(Error::UnsupportedVersion(*version))
+        }
+        Some(_) => (),
+    }
+    if envelope.len() < HEADER_LEN {
+        return Err(Error::InvalidEnvelope("payload too short".to_string()));
+    }
+
+    let count = u16::from_be_bytes(envelope[33..35].try_into().unwrap()) as usize;
+    let content_offset = HEADER_LEN + count * RECIPIENT_LEN;
+    if envelope.len() < content_offset {
+        return Err(Error::InvalidEnvelope("truncated recipients".to_string()));
+    }
+
+    Ok(content_offset)
+}
+
+/// Converts the ed25519 public key to its X25519 (Montgomery) form
+fn x25519_public_key(public_key: &PublicKey) -> X25519PublicKey {
+    X25519PublicKey::from(public_key.verifying_key().to_montgomery().to_bytes())
+}
+
+/// Converts the ed25519 secret key to the X25519 secret matching [`x25519_public_key`], the
+/// secret is zeroized on drop
+fn x25519_secret(keypair: &Keypair) -> StaticSecret {
+    let mut secret_key = keypair.secret_key();
+    let scalar = SigningKey::from_bytes(&secret_key).to_scalar_bytes();
+    zeroize(&mut secret_key);
+
+    StaticSecret::from(scalar)
+}
+
+fn wrap_key(shared: &[u8; 32], ephemeral_public: &[u8; 32], recipient: &PublicKey) -> [u8; 32] {
+    let mut material = Vec::with_capacity(96);
+    material.extend_from_slice(shared);
+    material.extend_from_slice(ephemeral_public);
+    material.extend_from_slice(recipient.as_bytes());
+
+    let key = blake3::derive_key(WRAP_KEY_CONTEXT, &material);
+    zeroize(&mut material);
+    key
+}
+
+fn content_aad(header: &[u8], aad: &[u8]) -> Vec<u8> {
+    let mut content_aad = header.to_vec();
+    content_aad.extend_from_slice(aad);
+    content_aad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::crypto::DeterministicKeyGen;
+
+    #[test]
+    fn test_seal_open() {
+        let alice = Keypair::generate(Some(b"it is a seed for key generation!"));
+        let bob = Keypair::generate(Some(b"not a seed for a key generation!"));
+        let eve = Keypair::random();
+        let aad = b"owner/repo/path";
+
+        let envelope = seal(
+            b"shared secret",
+            &[alice.public_key(), bob.public_key()],
+            aad,
+        )
+        .unwrap();
+
+        assert_eq!(open(&envelope, &alice, aad).unwrap(), b"shared secret");
+        assert_eq!(open(&envelope, &bob, aad).unwrap(), b"shared secret");
+        assert!(matches!(
+            open(&envelope, &eve, aad),
+            Err(Error::NotARecipient)
+        ));
+        assert!(matches!(
+            open(&envelope, &bob, b"owner/repo/other"),
+            Err(Error::FailedToDecrypt)
+        ));
+        assert_eq!(
+            recipients(&envelope).unwrap(),
+            vec![alice.public_key(), bob.public_key()]
+        );
+
+        // Swapping in another recipient doesn't grant access
+        let mut tampered = envelope.clone();
+        tampered[HEADER_LEN..HEADER_LEN + 32].copy_from_slice(eve.public_key().as_bytes());
+        assert!(open(&tampered, &eve, aad).is_err());
+        assert!(matches!(
+            open(&tampered, &bob, aad),
+            Err(Error::FailedToDecrypt)
+        ));
+
+        let mut unknown_version = envelope.clone();
+        unknown_version[0] = 2;
+        assert!(matches!(
+            recipients(&unknown_version),
+            Err(Error::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            open(&unknown_version, &bob, aad),
+            Err(Error::UnsupportedVersion(2))
+        ));
+
+        assert!(matches!(seal(b"data", &[], aad), Err(Error::NoRecipients)));
+    }
+}
@@ -0,0 +1,192 @@
+use crate::error::SignedRecordError as Error;
+use crate::transport::crypto::{Keypair, PublicKey, Signature};
+use crate::utils::now;
+
+/// Version of the signed record envelope
+pub static SIGNED_RECORD_VERSION: u8 = 1;
+
+/// Context of the signed bytes, so record signatures can't be replayed as other signatures
+static SIGNED_RECORD_CONTEXT: &[u8] = b"pubky:signed-record";
+
+/// Payload signed by its author, verifiable without trusting the homeserver serving it.
+///
+/// The signature covers the location of the record, the timestamp and the blake3 hash of the
+/// payload. The envelope is
+/// `version || timestamp || path length || path || payload hash || signature || payload`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedRecord {
+    /// Location of the record, `<repo>/<path>`
+    pub path: String,
+    /// Unix timestamp (seconds) of signing
+    pub timestamp: u64,
+    pub payload_hash: [u8; 32],
+    pub signature: Signature,
+    pub payload: Vec<u8>,
+}
+
+impl SignedRecord {
+    /// Signs the payload stored at the location
+    pub fn sign(keypair: &Keypair, path: &str, payload: &[u8]) -> Result<SignedRecord, Error> {
+        path_len(path)?;
+        let timestamp = now();
+        let payload_hash = *blake3::hash(payload).as_bytes();
+        let signature = keypair.sign(&signable(path, timestamp, &payload_hash));
+
+        Ok(SignedRecord {
+            path: path.to_string(),
+            timestamp,
+            payload_hash,
+            signature,
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Checks the record was signed by the public key for the location it was read from
+    pub fn verify(&self, public_key: &PublicKey, path: &str) -> Result<(), Error> {
+        if self.path != path {
+            return Err(Error::PathMismatch {
+                expected: path.to_string(),
+                found: self.path.clone(),
+            });
+        }
+        if blake3::hash(&self.payload).as_bytes() != &self.payload_hash {
+            return Err(Error::PayloadHashMismatch);
+        }
+
+        public_key
+            .verify(
+                &signable(&self.path, self.timestamp, &self.payload_hash),
+                &self.signature,
+            )
+            .map_err(|_| Error::InvalidSignature)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let path = self.path.as_bytes();
+        let path_len = path_len(&self.path)?;
+
+        let mut bytes = Vec::with_capacity(1 + 8 + 2 + path.len() + 32 + 64 + self.payload.len());
+        bytes.push(SIGNED_RECORD_VERSION);
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        bytes.extend_from_slice(&path_len.to_be_bytes());
+        bytes.extend_from_slice(path);
+        bytes.extend_from_slice(&self.payload_hash);
+        bytes.extend_from_slice(&self.signature.to_bytes());
+        bytes.extend_from_slice(&self.payload);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SignedRecord, Error> {
+        match bytes.first() {
+            None => return Err(Error::InvalidRecord("empty payload".to_string())),
+            Some(version) if *version != SIGNED_RECORD_VERSION => {
+                return Err(Error::UnsupportedVersion(*version))
+            }
+            Some(_) => (),
+        }
+        if bytes.len() < 11 {
+            return Err(Error::InvalidRecord("payload too short".to_string()));
+        }
+
+        let timestamp = u64::from_be_bytes(bytes[1..9].try_into().unwrap());
+        let path_len = u16::from_be_bytes(bytes[9..11].try_into().unwrap()) as usize;
+        let payload_offset = 11 + path_len + 32 + 64;
+        if bytes.len() < payload_offset {
+            return Err(Error::InvalidRecord("payload too short".to_string()));
+        }
+
+        let path = String::from_utf8(bytes[11..11 + path_len].to_vec())
+            .map_err(|e| Error::InvalidRecord(e.to_string()))?;
+        let offset = 11 + path_len;
+        let payload_hash = bytes[offset..offset + 32].try_into().unwrap();
+        let signature: [u8; 64] = bytes[offset + 32..payload_offset].try_into().unwrap();
+
+        Ok(SignedRecord {
+            path,
+            timestamp,
+            payload_hash,
+            signature: Signature::from_bytes(&signature),
+            payload: bytes[payload_offset..].to_vec(),
+        })
+    }
+}
+
+/// Location of a record within the author's repositories
+pub fn record_path(repo_name: &str, path: &str) -> String {
+    format!("{}/{}", repo_name, path)
+}
+
+/// Length of the path as stored in the envelope
+fn path_len(path: &str) -> Result<u16, Error> {
+    u16::try_from(path.len()).map_err(|_| Error::PathTooLong(path.len()))
+}
+
+fn signable(path: &str, timestamp: u64, payload_hash: &[u8; 32]) -> Vec<u8> {
+    let mut signable = SIGNED_RECORD_CONTEXT.to_vec();
+    signable.push(SIGNED_RECORD_VERSION);
+    signable.extend_from_slice(&timestamp.to_be_bytes());
+    signable.extend_from_slice(&(path.len() as u64).to_be_bytes());
+    signable.extend_from_slice(path.as_bytes());
+    signable.extend_from_slice(payload_hash);
+    signable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::crypto::DeterministicKeyGen;
+
+    #[test]
+    fn test_signed_record() {
+        let keypair = Keypair::generate(Some(b"it is a seed for key generation!"));
+        let path = record_path("repo", "post");
+
+        let record = SignedRecord::sign(&keypair, &path, b"hello").unwrap();
+        let decoded = SignedRecord::from_bytes(&record.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded, record);
+        decoded.verify(&keypair.public_key(), &path).unwrap();
+
+        assert!(matches!(
+            decoded.verify(&Keypair::random().public_key(), &path),
+            Err(Error::InvalidSignature)
+        ));
+        assert!(matches!(
+            decoded.verify(&keypair.public_key(), "repo/other"),
+            Err(Error::PathMismatch { .. })
+        ));
+
+        let mut forged = decoded.clone();
+        forged.payload = b"forged".to_vec();
+        assert!(matches!(
+            forged.verify(&keypair.public_key(), &path),
+            Err(Error::PayloadHashMismatch)
+        ));
+        forged.payload_hash = *blake3::hash(b"forged").as_bytes();
+        assert!(matches!(
+            forged.verify(&keypair.public_key(), &path),
+            Err(Error::InvalidSignature)
+        ));
+
+        let mut forged = decoded.clone();
+        forged.timestamp += 1;
+        assert!(forged.verify(&keypair.public_key(), &path).is_err());
+
+        assert!(matches!(
+            SignedRecord::from_bytes(b"hello"),
+            Err(Error::UnsupportedVersion(_))
+        ));
+        assert!(matches!(
+            SignedRecord::from_bytes(&record.to_bytes().unwrap()[..20]),
+            Err(Error::InvalidRecord(_))
+        ));
+
+        let long_path = "a".repeat(u16::MAX as usize + 1);
+        assert!(matches!(
+            SignedRecord::sign(&keypair, &long_path, b"hello"),
+            Err(Error::PathTooLong(65536))
+        ));
+        let mut long = record.clone();
+        long.path = long_path;
+        assert!(matches!(long.to_bytes(), Err(Error::PathTooLong(65536))));
+    }
+}
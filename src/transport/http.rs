@@ -1,44 +1,140 @@
 use crate::error::HTTPError as Error;
+use crate::transport::cookies::SessionStore;
+pub use bytes::Bytes;
 use reqwest::blocking::Client;
 pub use reqwest::header::HeaderMap;
+use reqwest::header::COOKIE;
 pub use reqwest::Method;
 pub use reqwest::Url;
+use std::time::Duration;
 
-// Have a hashmap homeserverUrl -> session_id
-// Q: how to clean it? -> delete manually
-pub fn request(
-    method: Method,
-    path: Url,
-    session_id: &mut Option<String>,
-    headers: Option<&HeaderMap>,
-    body: Option<String>,
-) -> Result<String, Error> {
-    // TODO: consider moving somewhere outside?
-    let client = Client::new();
-    let mut request_builder = client.request(method, path);
-
-    if let Some(body) = body {
-        request_builder = request_builder.body(body);
+pub(crate) static DEFAULT_USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Configuration of the HTTP transport used for requests to homeservers
+#[derive(Debug, Clone, Default)]
+pub struct HttpConfig {
+    /// Timeout of the whole request
+    pub timeout: Option<Duration>,
+    /// Timeout of establishing a connection
+    pub connect_timeout: Option<Duration>,
+    /// Defaults to `pdk/<version>`
+    pub user_agent: Option<String>,
+    /// Proxy used for all requests
+    pub proxy: Option<Url>,
+    /// Maximum number of idle connections kept in the pool per host.
+    ///
+    /// Only idle connections are limited, concurrent requests still open as many connections as
+    /// they need: reqwest has no hard cap on open connections.
+    pub max_idle_connections: Option<usize>,
+}
+
+/// HTTP transport holding a single connection pool and the session store.
+///
+/// It is cheap to clone, clones share the same pool and sessions, so connections, TLS sessions
+/// and logins are reused across all requests of the client.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    sessions: SessionStore,
+}
+
+impl HttpClient {
+    pub fn new(config: &HttpConfig) -> Result<HttpClient, Error> {
+        let mut builder = Client::builder().user_agent(
+            config
+                .user_agent
+                .clone()
+                .unwrap_or(DEFAULT_USER_AGENT.to_string()),
+        );
+
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = config.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(proxy) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy.clone())
+                .map_err(|e| Error::InvalidConfig(e.to_string()))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(max_idle_connections) = config.max_idle_connections {
+            builder = builder.pool_max_idle_per_host(max_idle_connections);
+        }
+
+        match builder.build() {
+            Ok(client) => Ok(HttpClient {
+                client,
+                sessions: SessionStore::new(),
+            }),
+            Err(e) => Err(Error::InvalidConfig(e.to_string())),
+        }
     }
 
-    if let Some(session_id) = session_id {
-        request_builder = request_builder.header("cookie", format!("sessionId={}", session_id));
+    /// Use the given session store instead of a fresh one
+    pub fn with_sessions(mut self, sessions: SessionStore) -> Self {
+        self.sessions = sessions;
+        self
     }
 
-    if let Some(headers) = headers {
-        request_builder = request_builder.headers(headers.clone());
+    /// Sessions of all users known to this transport
+    pub fn sessions(&self) -> &SessionStore {
+        &self.sessions
     }
-    request_builder = request_builder.header("credentials", "include");
 
-    match request_builder.send() {
-        Ok(res) => {
-            let found_session_id = res.cookies().find(|c| c.name() == "sessionId");
-            if let Some(s_id) = found_session_id {
-                *session_id = Some(s_id.value().to_string());
+    /// Sends the request, on behalf of `user_id` if given: the user's session cookie for the
+    /// homeserver is attached and a session set by the response is stored for the user
+    pub fn request(
+        &self,
+        method: Method,
+        path: Url,
+        user_id: Option<&str>,
+        headers: Option<&HeaderMap>,
+        body: Option<Vec<u8>>,
+    ) -> Result<Bytes, Error> {
+        let mut request_builder = self.client.request(method, path.clone());
+
+        if let Some(body) = body {
+            request_builder = request_builder.body(body);
+        }
+
+        if let Some(cookie) =
+            user_id.and_then(|user_id| self.sessions.cookie_header(&path, user_id))
+        {
+            request_builder = request_builder.header(COOKIE, cookie);
+        }
+
+        if let Some(headers) = headers {
+            request_builder = request_builder.headers(headers.clone());
+        }
+
+        match request_builder.send() {
+            Ok(res) => {
+                let status = res.status();
+                if !status.is_success() {
+                    return Err(Error::ResponseFailed {
+                        status: status.as_u16(),
+                        reason: status.canonical_reason().unwrap_or_default().to_string(),
+                        body: String::from_utf8_lossy(&res.bytes().unwrap_or_default()).to_string(),
+                    });
+                }
+
+                if let Some(user_id) = user_id {
+                    self.sessions
+                        .store_response_cookies(&path, user_id, res.cookies());
+                }
+                res.bytes()
+                    .map_err(|err| Error::RequestFailed(err.to_string()))
             }
-            Ok(res.text().unwrap())
+            Err(err) => Err(Error::RequestFailed(err.to_string())),
         }
-        Err(err) => Err(Error::RequestFailed(err.to_string())),
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        HttpClient::new(&HttpConfig::default()).expect("Failed to build default HTTP client")
     }
 }
 
@@ -58,16 +154,96 @@ mod tests {
         };
         let server = test_utils::create_server(vec![dummy_test_mock_params]);
 
-        let mut session_id = None;
+        let http = HttpClient::default();
         let headers = HeaderMap::new();
         let body = None;
         let path = Url::parse(&format!("{}/test", server.url())).unwrap();
 
-        let res = request(Method::GET, path, &mut session_id, Some(&headers), body);
+        let res = http.request(
+            Method::GET,
+            path.clone(),
+            Some("user"),
+            Some(&headers),
+            body,
+        );
 
         assert!(res.is_ok());
-        assert!(session_id.is_some());
-        assert_eq!(session_id.unwrap(), "123");
+        let session = http.sessions().get(&path, "user").unwrap();
+        assert_eq!(session.value, "123");
+        assert_eq!(session.path, "/");
+        assert!(http.sessions().get(&path, "other_user").is_none());
         assert_eq!(res.unwrap(), "test");
     }
+
+    #[test]
+    fn test_request_failed_status() {
+        let not_found_mock_params = test_utils::HttpMockParams {
+            method: &Method::GET,
+            path: "/missing",
+            status: 404,
+            body: &b"no such file".to_vec(),
+            headers: vec![("Set-Cookie", "sessionId=123")],
+        };
+        let server = test_utils::create_server(vec![not_found_mock_params]);
+
+        let http = HttpClient::default();
+        let path = Url::parse(&format!("{}/missing", server.url())).unwrap();
+
+        let err = http
+            .request(Method::GET, path, Some("user"), None, None)
+            .unwrap_err();
+
+        assert!(http.sessions().list().is_empty());
+        assert_eq!(err.status(), Some(404));
+        assert_eq!(err.kind(), Some(crate::error::HTTPErrorKind::NotFound));
+        match err {
+            Error::ResponseFailed {
+                status,
+                reason,
+                body,
+            } => {
+                assert_eq!(status, 404);
+                assert_eq!(reason, "Not Found");
+                assert_eq!(body, "no such file");
+            }
+            _ => panic!("Expected failed response"),
+        }
+    }
+
+    #[test]
+    fn test_http_config() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/agent")
+            .match_header("user-agent", "test-agent")
+            .with_body("ok")
+            .create();
+        server
+            .mock("GET", "/agent")
+            .match_header("user-agent", DEFAULT_USER_AGENT)
+            .with_status(403)
+            .create();
+
+        let http = HttpClient::new(&HttpConfig {
+            user_agent: Some("test-agent".to_string()),
+            timeout: Some(Duration::from_secs(5)),
+            max_idle_connections: Some(1),
+            ..Default::default()
+        })
+        .unwrap();
+        let path = Url::parse(&format!("{}/agent", server.url())).unwrap();
+
+        // Both requests go through the same pool
+        for _ in 0..2 {
+            let res = http.request(Method::GET, path.clone(), None, None, None);
+            assert_eq!(res.unwrap(), "ok");
+        }
+
+        // Default user agent is not let through
+        let err = HttpClient::default()
+            .request(Method::GET, path, None, None, None)
+            .unwrap_err();
+        assert_eq!(err.status(), Some(403));
+        assert_eq!(err.kind(), Some(crate::error::HTTPErrorKind::Forbidden));
+    }
 }
@@ -1,5 +1,7 @@
 pub mod auth;
 pub mod challenge;
+pub mod cookies;
 pub mod crypto;
 pub mod http;
 pub mod resolver;
+pub mod session;
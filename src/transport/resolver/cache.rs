@@ -0,0 +1,428 @@
+use super::store::PacketStore;
+use super::{homeserver_key, homeserver_ttl, homeserver_url, record_ttl};
+use crate::error::DHTError as Error;
+use crate::transport::http::Url;
+use crate::utils::now;
+use pkarr::{PublicKey, SignedPacket};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Time a failed lookup is remembered for by default
+pub static DEFAULT_NEGATIVE_TTL: Duration = Duration::from_secs(30);
+
+/// Longest time a homeserver is cached for, whatever the TTL its records were published with
+pub static MAX_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Cached result of a homeserver lookup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cached {
+    /// Homeserver URL of the user
+    Found(Url),
+    /// Nothing usable is published for the user
+    NotFound,
+}
+
+impl Cached {
+    pub(crate) fn into_result(self, public_key: &PublicKey) -> Result<Url, Error> {
+        match self {
+            Cached::Found(url) => Ok(url),
+            Cached::NotFound => Err(Error::EntryNotFound(public_key.to_string())),
+        }
+    }
+}
+
+/// Cached lookup result with its expiration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub value: Cached,
+    /// Unix timestamp (seconds) the entry is valid until
+    pub expires_at: u64,
+}
+
+/// How the resolver uses its cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheStrategy {
+    /// Look up missing and expired entries before returning
+    #[default]
+    ReadThrough,
+    /// Read-through, and refresh entries in the background once they expire within
+    /// `refresh_before`, so hot entries never expire
+    ReadAhead { refresh_before: Duration },
+    /// Return entries up to `max_stale` past their expiration right away, refreshing them in the
+    /// background
+    StaleWhileRevalidate { max_stale: Duration },
+}
+
+/// What the resolver does with the cached entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CacheDecision {
+    Hit(Cached),
+    /// Return the cached value and refresh it in the background
+    HitAndRefresh(Cached),
+    Miss,
+}
+
+impl CacheStrategy {
+    pub(crate) fn decide(&self, entry: Option<CacheEntry>, now: u64) -> CacheDecision {
+        let entry = match entry {
+            Some(entry) => entry,
+            None => return CacheDecision::Miss,
+        };
+        let live = entry.expires_at > now;
+
+        match self {
+            CacheStrategy::ReadThrough if live => CacheDecision::Hit(entry.value),
+            CacheStrategy::ReadAhead { refresh_before } if live => {
+                if entry.expires_at <= now.saturating_add(refresh_before.as_secs()) {
+                    CacheDecision::HitAndRefresh(entry.value)
+                } else {
+                    CacheDecision::Hit(entry.value)
+                }
+            }
+            CacheStrategy::StaleWhileRevalidate { .. } if live => CacheDecision::Hit(entry.value),
+            CacheStrategy::StaleWhileRevalidate { max_stale }
+                if entry.expires_at.saturating_add(max_stale.as_secs()) > now =>
+            {
+                CacheDecision::HitAndRefresh(entry.value)
+            }
+            _ => CacheDecision::Miss,
+        }
+    }
+}
+
+/// Cache of resolved homeservers.
+///
+/// Entries expire with the TTLs of the DNS records they were read from, failed lookups are
+/// remembered for `negative_ttl`. Clones share the same entries.
+///
+/// With a [`PacketStore`] the resolved packets are kept as well, entries missing in memory are
+/// restored from them, e.g. after a restart.
+#[derive(Debug, Clone)]
+pub struct ResolverCache {
+    entries: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    refreshing: Arc<Mutex<HashSet<String>>>,
+    negative_ttl: Duration,
+    packets: Option<PacketStore>,
+}
+
+impl Default for ResolverCache {
+    fn default() -> Self {
+        ResolverCache {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            refreshing: Arc::new(Mutex::new(HashSet::new())),
+            negative_ttl: DEFAULT_NEGATIVE_TTL,
+            packets: None,
+        }
+    }
+}
+
+impl ResolverCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time failed lookups are remembered for, zero disables negative caching
+    pub fn negative_ttl(mut self, negative_ttl: Duration) -> Self {
+        self.negative_ttl = negative_ttl;
+        self
+    }
+
+    /// Keep resolved packets in the store, e.g. one persisted on disk
+    pub fn with_packet_store(mut self, packets: PacketStore) -> Self {
+        self.packets = Some(packets);
+        self
+    }
+
+    pub fn packet_store(&self) -> Option<&PacketStore> {
+        self.packets.as_ref()
+    }
+
+    /// Live entry of the user, expired entries are dropped
+    pub fn get(&self, public_key: &PublicKey) -> Option<Cached> {
+        let mut entries = self.entries.lock().unwrap();
+        let key = public_key.to_string();
+
+        match entries.get(&key) {
+            Some(entry) if entry.expires_at > now() => Some(entry.value.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Entry of the user, including an expired one
+    pub fn entry(&self, public_key: &PublicKey) -> Option<CacheEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&public_key.to_string())
+            .cloned()
+    }
+
+    /// Caches the homeserver URL of the user for the TTL of its records
+    pub fn insert(&self, public_key: &PublicKey, homeserver_url: Url, ttl: Duration) {
+        self.set(public_key, Cached::Found(homeserver_url), ttl);
+    }
+
+    /// Remembers that the lookup of the user failed, stored packets of the user are dropped so
+    /// they are not restored once the failure expires
+    pub fn insert_not_found(&self, public_key: &PublicKey) {
+        if self.negative_ttl.is_zero() {
+            self.invalidate(public_key);
+            return;
+        }
+        self.set(public_key, Cached::NotFound, self.negative_ttl);
+        if let Some(packets) = &self.packets {
+            packets.remove(public_key);
+        }
+    }
+
+    /// Drops the entry of the user, so the next lookup goes to the network
+    pub fn invalidate(&self, public_key: &PublicKey) {
+        self.entries.lock().unwrap().remove(&public_key.to_string());
+        if let Some(packets) = &self.packets {
+            packets.remove(public_key);
+        }
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+        if let Some(packets) = &self.packets {
+            packets.clear();
+        }
+    }
+
+    /// Restores the entry of the user from the stored packets, valid as long as both are
+    pub(crate) fn load(&self, public_key: &PublicKey) -> Option<Url> {
+        let packets = self.packets.as_ref()?;
+        let (packet, expires_at) = packets.entry(public_key)?;
+        let (homeserver_packet, homeserver_expires_at) =
+            packets.entry(&homeserver_key(&packet).ok()?)?;
+        let url = homeserver_url(&homeserver_packet).ok()?;
+
+        self.entries.lock().unwrap().insert(
+            public_key.to_string(),
+            CacheEntry {
+                value: Cached::Found(url.clone()),
+                expires_at: expires_at.min(homeserver_expires_at),
+            },
+        );
+
+        Some(url)
+    }
+
+    /// Stores the packets the homeserver was resolved from, if there is a packet store
+    pub(crate) fn store_packets(&self, packet: &SignedPacket, homeserver_packet: &SignedPacket) {
+        if let Some(packets) = &self.packets {
+            // Best effort, the in-memory entry is enough for this process
+            let _ = packets.insert(packet, record_ttl(packet, "_pubky"));
+            let _ = packets.insert(homeserver_packet, record_ttl(homeserver_packet, "@"));
+        }
+    }
+
+    /// Caches the result of a lookup (user's and homeserver's packets), failures only if nothing
+    /// is published for the user
+    pub(crate) fn update(
+        &self,
+        public_key: &PublicKey,
+        result: Result<(SignedPacket, SignedPacket), Error>,
+    ) -> Result<Url, Error> {
+        let result = result.and_then(|(packet, homeserver_packet)| {
+            let url = homeserver_url(&homeserver_packet)?;
+            self.store_packets(&packet, &homeserver_packet);
+            Ok((url, homeserver_ttl(&packet, &homeserver_packet)))
+        });
+
+        match result {
+            Ok((url, ttl)) => {
+                self.insert(public_key, url.clone(), ttl);
+                Ok(url)
+            }
+            Err(e) => {
+                if matches!(e, Error::EntryNotFound(_) | Error::NoRecordsFound) {
+                    self.insert_not_found(public_key);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Caches the result of a background refresh, a failed refresh keeps the entry being
+    /// refreshed, so it is still served until it expires
+    pub(crate) fn refreshed(
+        &self,
+        public_key: &PublicKey,
+        result: Result<(SignedPacket, SignedPacket), Error>,
+    ) {
+        if result.is_ok() {
+            let _ = self.update(public_key, result);
+        }
+        self.end_refresh(public_key);
+    }
+
+    /// Marks the entry as being refreshed, `false` if a refresh is already running
+    pub(crate) fn begin_refresh(&self, public_key: &PublicKey) -> bool {
+        self.refreshing
+            .lock()
+            .unwrap()
+            .insert(public_key.to_string())
+    }
+
+    pub(crate) fn end_refresh(&self, public_key: &PublicKey) {
+        self.refreshing
+            .lock()
+            .unwrap()
+            .remove(&public_key.to_string());
+    }
+
+    fn set(&self, public_key: &PublicKey, value: Cached, ttl: Duration) {
+        self.entries.lock().unwrap().insert(
+            public_key.to_string(),
+            CacheEntry {
+                value,
+                expires_at: now().saturating_add(ttl.as_secs()),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pkarr::Keypair;
+
+    #[test]
+    fn test_resolver_cache() {
+        let public_key = Keypair::random().public_key();
+        let url = Url::parse("https://datastore.example.com").unwrap();
+        let cache = ResolverCache::new();
+
+        assert_eq!(cache.get(&public_key), None);
+
+        cache.insert(&public_key, url.clone(), Duration::from_secs(30));
+        assert_eq!(cache.get(&public_key), Some(Cached::Found(url.clone())));
+        assert_eq!(
+            cache.clone().get(&public_key),
+            Some(Cached::Found(url.clone()))
+        );
+
+        cache.invalidate(&public_key);
+        assert_eq!(cache.get(&public_key), None);
+
+        // Never expires, without overflowing
+        cache.insert(&public_key, url.clone(), Duration::MAX);
+        assert_eq!(cache.entry(&public_key).unwrap().expires_at, u64::MAX);
+
+        // Expired at once
+        cache.insert(&public_key, url.clone(), Duration::ZERO);
+        assert_eq!(cache.get(&public_key), None);
+
+        cache.insert_not_found(&public_key);
+        assert_eq!(cache.get(&public_key), Some(Cached::NotFound));
+
+        let cache = ResolverCache::new().negative_ttl(Duration::ZERO);
+        cache.insert_not_found(&public_key);
+        assert_eq!(cache.get(&public_key), None);
+    }
+
+    #[test]
+    fn test_resolver_cache_not_found() {
+        use crate::test_utils::TempDir;
+        use crate::transport::resolver::homeserver_packet;
+        let dir = TempDir::new("pdk-packets");
+
+        let key = Keypair::random();
+        let public_key = key.public_key();
+        let url = Url::parse("https://datastore.example.com").unwrap();
+        let cache = ResolverCache::new().with_packet_store(PacketStore::open(dir.path()).unwrap());
+
+        let packets = (
+            homeserver_packet(&key, &url).unwrap(),
+            homeserver_packet(&key, &url).unwrap(),
+        );
+        assert_eq!(cache.update(&public_key, Ok(packets)).unwrap(), url);
+        assert!(cache.packet_store().unwrap().get(&public_key).is_some());
+
+        // Unpublished user is not restored from the packets stored before
+        assert!(cache
+            .update(
+                &public_key,
+                Err(Error::EntryNotFound(public_key.to_string()))
+            )
+            .is_err());
+        assert_eq!(cache.get(&public_key), Some(Cached::NotFound));
+        assert!(cache.packet_store().unwrap().get(&public_key).is_none());
+        let restarted =
+            ResolverCache::new().with_packet_store(PacketStore::open(dir.path()).unwrap());
+        assert_eq!(restarted.load(&public_key), None);
+    }
+
+    #[test]
+    fn test_cache_strategy() {
+        let url = Url::parse("https://datastore.example.com").unwrap();
+        let found = Cached::Found(url);
+        let entry = |expires_at| {
+            Some(CacheEntry {
+                value: found.clone(),
+                expires_at,
+            })
+        };
+        let now = 1000;
+
+        let read_through = CacheStrategy::ReadThrough;
+        assert_eq!(read_through.decide(None, now), CacheDecision::Miss);
+        assert_eq!(
+            read_through.decide(entry(now + 10), now),
+            CacheDecision::Hit(found.clone())
+        );
+        assert_eq!(read_through.decide(entry(now), now), CacheDecision::Miss);
+
+        let read_ahead = CacheStrategy::ReadAhead {
+            refresh_before: Duration::from_secs(10),
+        };
+        assert_eq!(
+            read_ahead.decide(entry(now + 60), now),
+            CacheDecision::Hit(found.clone())
+        );
+        assert_eq!(
+            read_ahead.decide(entry(now + 5), now),
+            CacheDecision::HitAndRefresh(found.clone())
+        );
+        assert_eq!(read_ahead.decide(entry(now - 5), now), CacheDecision::Miss);
+
+        let stale_while_revalidate = CacheStrategy::StaleWhileRevalidate {
+            max_stale: Duration::from_secs(60),
+        };
+        assert_eq!(
+            stale_while_revalidate.decide(entry(now + 5), now),
+            CacheDecision::Hit(found.clone())
+        );
+        assert_eq!(
+            stale_while_revalidate.decide(entry(now - 30), now),
+            CacheDecision::HitAndRefresh(found.clone())
+        );
+        assert_eq!(
+            stale_while_revalidate.decide(entry(now - 60), now),
+            CacheDecision::Miss
+        );
+
+        // Unbounded durations saturate instead of overflowing
+        let read_ahead = CacheStrategy::ReadAhead {
+            refresh_before: Duration::MAX,
+        };
+        assert_eq!(
+            read_ahead.decide(entry(u64::MAX), now),
+            CacheDecision::HitAndRefresh(found.clone())
+        );
+        let stale_while_revalidate = CacheStrategy::StaleWhileRevalidate {
+            max_stale: Duration::MAX,
+        };
+        assert_eq!(
+            stale_while_revalidate.decide(entry(now - 60), now),
+            CacheDecision::HitAndRefresh(found.clone())
+        );
+    }
+}
@@ -0,0 +1,583 @@
+use crate::error::DHTError as Error;
+use crate::utils::now;
+use pkarr::{dns, Keypair, PkarrClient, PublicKey, SignedPacket};
+use reqwest::Url;
+use std::time::Duration;
+
+pub mod cache;
+pub mod store;
+
+use cache::{CacheDecision, CacheStrategy, ResolverCache, MAX_TTL};
+
+#[derive(Clone)]
+pub struct Resolver<'a> {
+    relay_url: Option<&'a Url>,
+    // NOTE: Cache is needed mostly for DHT lookups. It will be implemented in pkarr v2
+    // So cache could be removed after update
+    cache: ResolverCache,
+    strategy: CacheStrategy,
+    bootstrap: Option<&'a Vec<String>>,
+}
+
+impl Resolver<'_> {
+    /// Creates a new resolver, if relay_url is None, it will publish to DHT
+    pub fn new<'a>(relay_url: Option<&'a Url>, bootstrap: Option<&'a Vec<String>>) -> Resolver<'a> {
+        Resolver {
+            relay_url,
+            cache: ResolverCache::new(),
+            strategy: CacheStrategy::default(),
+            bootstrap,
+        }
+    }
+
+    /// How lookups use the cache, read-through by default
+    pub fn with_strategy(mut self, strategy: CacheStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Use the cache, e.g. one shared with other resolvers
+    pub fn with_cache(mut self, cache: ResolverCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn cache(&self) -> &ResolverCache {
+        &self.cache
+    }
+
+    /// Forget the cached homeserver of the user, e.g. after it moved to another homeserver
+    pub fn invalidate(&self, public_key: &PublicKey) {
+        self.cache.invalidate(public_key);
+    }
+
+    /// Resolves home server url using DHT or relay (with name '_pubky')
+    pub fn resolve_homeserver(
+        &mut self,
+        public_key: &PublicKey,
+        relay_url: Option<&Url>,
+    ) -> Result<Url, Error> {
+        let relay_url = relay_url.or(self.relay_url);
+
+        match self.strategy.decide(self.cache.entry(public_key), now()) {
+            CacheDecision::Hit(cached) => return cached.into_result(public_key),
+            CacheDecision::HitAndRefresh(cached) => {
+                self.refresh(public_key, relay_url);
+                return cached.into_result(public_key);
+            }
+            CacheDecision::Miss => (),
+        }
+
+        if let Some(url) = self.cache.load(public_key) {
+            return Ok(url);
+        }
+
+        self.cache
+            .update(public_key, resolve(public_key, relay_url, self.bootstrap))
+    }
+
+    /// Publish record to relay or DHT
+    pub fn publish(
+        &mut self,
+        key_pair: &Keypair,
+        homeserver_url: &Url,
+        relay_url: Option<&Url>,
+    ) -> Result<(), Error> {
+        let client = pkarr_client(self.bootstrap);
+        let signed_packet = homeserver_packet(key_pair, homeserver_url)?;
+
+        let res = match relay_url {
+            Some(relay_url) => client.relay_put(relay_url, &signed_packet),
+            None => match &self.relay_url {
+                Some(relay_url) => client.relay_put(relay_url, &signed_packet),
+                None => {
+                    let _ = client.publish(&signed_packet);
+                    Ok(())
+                }
+            },
+        };
+
+        match res {
+            Ok(_) => {
+                self.cache.insert(
+                    &key_pair.public_key(),
+                    homeserver_url.clone(),
+                    homeserver_ttl(&signed_packet, &signed_packet),
+                );
+                self.cache.store_packets(&signed_packet, &signed_packet);
+                Ok(())
+            }
+            Err(e) => Err(Error::EntryNotPublished(e.to_string())),
+        }
+    }
+
+    /// Refreshes the cached entry in a background thread
+    fn refresh(&self, public_key: &PublicKey, relay_url: Option<&Url>) {
+        if !self.cache.begin_refresh(public_key) {
+            return;
+        }
+
+        let cache = self.cache.clone();
+        let public_key = public_key.clone();
+        let relay_url = relay_url.cloned();
+        let bootstrap = self.bootstrap.cloned();
+        std::thread::spawn(move || {
+            let result = resolve(&public_key, relay_url.as_ref(), bootstrap.as_ref());
+            cache.refreshed(&public_key, result);
+        });
+    }
+}
+
+/// Looks up the packets of the user and of its homeserver, bypassing the cache
+fn resolve(
+    public_key: &PublicKey,
+    relay_url: Option<&Url>,
+    bootstrap: Option<&Vec<String>>,
+) -> Result<(SignedPacket, SignedPacket), Error> {
+    let packet = lookup(public_key, relay_url, bootstrap)?;
+    let homeserver_key = homeserver_key(&packet)?;
+    let homeserver_packet = lookup(&homeserver_key, relay_url, bootstrap)?;
+
+    Ok((packet, homeserver_packet))
+}
+
+/// Looks up a public key in the relay or DHT
+fn lookup(
+    public_key: &PublicKey,
+    relay_url: Option<&Url>,
+    bootstrap: Option<&Vec<String>>,
+) -> Result<SignedPacket, Error> {
+    let not_found = || Error::EntryNotFound(public_key.to_string());
+
+    let entry = match relay_url {
+        Some(relay_url) => pkarr_client(bootstrap)
+            .relay_get(relay_url, public_key.clone())
+            .map_err(|e| Error::LookupFailed(e.to_string()))?,
+        None => pkarr_client(bootstrap).resolve_most_recent(public_key.clone()),
+    };
+
+    entry.ok_or_else(not_found)
+}
+
+fn pkarr_client(bootstrap: Option<&Vec<String>>) -> PkarrClient {
+    match bootstrap {
+        Some(bootstrap) => PkarrClient::builder().bootstrap(bootstrap).build(),
+        None => PkarrClient::new(),
+    }
+}
+
+/// Extracts public key of the homeserver from user's packet (with name '_pubky')
+pub(crate) fn homeserver_key(packet: &SignedPacket) -> Result<PublicKey, Error> {
+    for record in packet.resource_records("_pubky") {
+        if let dns::rdata::RData::TXT(txt) = &record.rdata {
+            // See https://docs.rs/simple-dns/latest/simple_dns/rdata/struct.TXT.html#method.attributes
+            for (k, v) in txt.attributes() {
+                if !k.starts_with("home") {
+                    continue;
+                }
+
+                return match v {
+                    None => Err(Error::NoRecordsFound),
+                    Some(v) => v.as_str().try_into().map_err(|_| Error::NoRecordsFound),
+                };
+            }
+        }
+    }
+
+    Err(Error::NoRecordsFound)
+}
+
+/// Extracts home server url from homeserver's packet (with name '@')
+pub(crate) fn homeserver_url(packet: &SignedPacket) -> Result<Url, Error> {
+    for record in packet.resource_records("@") {
+        match &record.rdata {
+            dns::rdata::RData::CNAME(cname) => {
+                // See https://docs.rs/simple-dns/latest/simple_dns/rdata/struct.CNAME.html#fields
+                // Url::parse(format!("https://{}", cname.0.to_string()).as_str())
+                return Url::parse(&cname.0.to_string()).map_err(|_| Error::NoRecordsFound);
+            }
+            dns::rdata::RData::TXT(txt) => {
+                // See https://docs.rs/simple-dns/latest/simple_dns/rdata/struct.TXT.html#method.attributes
+                for (k, v) in txt.attributes() {
+                    if !k.starts_with("localhost") {
+                        continue;
+                    }
+                    let url = match v {
+                        // Url::parse(format!("http://{k}{v}").as_str())
+                        Some(v) => Url::parse(format!("{k}{v}").as_str()),
+                        // Url::parse(format!("http://{k}").as_str())
+                        None => Url::parse(k.as_str()),
+                    };
+                    return url.map_err(|_| Error::NoRecordsFound);
+                }
+            }
+            _ => continue,
+        }
+    }
+
+    Err(Error::NoRecordsFound)
+}
+
+/// Time the homeserver URL stays valid, the shortest TTL of the records it was resolved from, at
+/// most [`MAX_TTL`]
+pub(crate) fn homeserver_ttl(packet: &SignedPacket, homeserver_packet: &SignedPacket) -> Duration {
+    let ttl = packet
+        .resource_records("_pubky")
+        .chain(homeserver_packet.resource_records("@"))
+        .map(|record| record.ttl)
+        .min()
+        .unwrap_or(0);
+
+    Duration::from_secs(ttl.into()).min(MAX_TTL)
+}
+
+/// Shortest TTL of the records with the name, zero if there are none, at most [`MAX_TTL`]
+pub(crate) fn record_ttl(packet: &SignedPacket, name: &str) -> Duration {
+    let ttl = packet
+        .resource_records(name)
+        .map(|record| record.ttl)
+        .min()
+        .unwrap_or(0);
+
+    Duration::from_secs(ttl.into()).min(MAX_TTL)
+}
+
+/// Creates signed packet which points user's identity to the homeserver url
+pub(crate) fn homeserver_packet(
+    key_pair: &Keypair,
+    homeserver_url: &Url,
+) -> Result<SignedPacket, Error> {
+    let mut packet = dns::Packet::new_reply(0);
+    let home = format!("home={}", &key_pair.public_key());
+    let home = home.as_str();
+
+    packet.answers.push(dns::ResourceRecord::new(
+        dns::Name::new("_pubky").unwrap(),
+        dns::CLASS::IN,
+        7200,
+        dns::rdata::RData::TXT(home.try_into().unwrap()),
+    ));
+
+    packet.answers.push(dns::ResourceRecord::new(
+        dns::Name::new("@").unwrap(),
+        dns::CLASS::IN,
+        30,
+        dns::rdata::RData::CNAME(dns::Name::new(homeserver_url.as_str()).unwrap().into()),
+    ));
+
+    SignedPacket::from_packet(key_pair, &packet)
+        .map_err(|e| Error::EntryNotPublished(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cache::Cached;
+    use std::thread::sleep;
+
+    /// Waits for a background refresh to cache the URL
+    fn wait_for_refresh(cache: &ResolverCache, public_key: &PublicKey, url: &Url) -> bool {
+        for _ in 0..100 {
+            if cache.get(public_key) == Some(Cached::Found(url.clone())) {
+                return true;
+            }
+            sleep(Duration::from_millis(100));
+        }
+        false
+    }
+
+    #[test]
+    fn test_resolve_homeserver_from_dht() {
+        use mainline::dht::Testnet;
+        let testnet = Testnet::new(10);
+
+        let key = Keypair::random();
+
+        let url = Url::parse("https://datastore.example.com").unwrap();
+
+        let mut resolver = Resolver::new(None, Some(&testnet.bootstrap));
+        resolver.publish(&key, &url, None).unwrap();
+        let res = resolver
+            .resolve_homeserver(&key.public_key(), None)
+            .unwrap();
+
+        assert_eq!(res.to_string(), url.to_string());
+    }
+
+    #[test]
+    fn test_resolve_homeserver_cache() {
+        use mainline::dht::Testnet;
+        let testnet = Testnet::new(10);
+
+        let key = Keypair::random();
+        let url = Url::parse("https://datastore.example.com").unwrap();
+        let moved_url = Url::parse("https://other-datastore.example.com").unwrap();
+
+        let mut resolver = Resolver::new(None, Some(&testnet.bootstrap));
+
+        // Not published yet, the failure is cached
+        assert!(matches!(
+            resolver.resolve_homeserver(&key.public_key(), None),
+            Err(Error::EntryNotFound(_))
+        ));
+        assert_eq!(
+            resolver.cache().get(&key.public_key()),
+            Some(Cached::NotFound)
+        );
+
+        Resolver::new(None, Some(&testnet.bootstrap))
+            .publish(&key, &url, None)
+            .unwrap();
+        assert!(resolver
+            .resolve_homeserver(&key.public_key(), None)
+            .is_err());
+
+        resolver.invalidate(&key.public_key());
+        assert_eq!(
+            resolver
+                .resolve_homeserver(&key.public_key(), None)
+                .unwrap(),
+            url
+        );
+
+        // Cached homeserver is kept until it expires or is invalidated
+        Resolver::new(None, Some(&testnet.bootstrap))
+            .publish(&key, &moved_url, None)
+            .unwrap();
+        assert_eq!(
+            resolver
+                .resolve_homeserver(&key.public_key(), None)
+                .unwrap(),
+            url
+        );
+        resolver.invalidate(&key.public_key());
+        assert_eq!(
+            resolver
+                .resolve_homeserver(&key.public_key(), None)
+                .unwrap(),
+            moved_url
+        );
+    }
+
+    #[test]
+    fn test_resolve_homeserver_relay_failure() {
+        let key = Keypair::random();
+        let mut relay = mockito::Server::new();
+        let failure = relay
+            .mock("GET", format!("/{}", key.public_key().to_z32()).as_str())
+            .with_status(503)
+            .create();
+        let relay_url = Url::parse(&relay.url()).unwrap();
+        let mut resolver = Resolver::new(Some(&relay_url), None);
+
+        // Outage of the relay is not remembered as a missing entry
+        assert!(matches!(
+            resolver.resolve_homeserver(&key.public_key(), None),
+            Err(Error::LookupFailed(_))
+        ));
+        assert_eq!(resolver.cache().get(&key.public_key()), None);
+
+        failure.remove();
+        relay
+            .mock("GET", format!("/{}", key.public_key().to_z32()).as_str())
+            .with_status(404)
+            .create();
+        assert!(matches!(
+            resolver.resolve_homeserver(&key.public_key(), None),
+            Err(Error::EntryNotFound(_))
+        ));
+        assert_eq!(
+            resolver.cache().get(&key.public_key()),
+            Some(Cached::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_resolve_homeserver_packet_store() {
+        use crate::test_utils::TempDir;
+        use mainline::dht::Testnet;
+        use store::PacketStore;
+        let testnet = Testnet::new(10);
+        let other_testnet = Testnet::new(3);
+        let dir = TempDir::new("pdk-packets");
+
+        let key = Keypair::random();
+        let url = Url::parse("https://datastore.example.com").unwrap();
+
+        Resolver::new(None, Some(&testnet.bootstrap))
+            .publish(&key, &url, None)
+            .unwrap();
+        let cache = ResolverCache::new().with_packet_store(PacketStore::open(dir.path()).unwrap());
+        let mut resolver = Resolver::new(None, Some(&testnet.bootstrap)).with_cache(cache);
+        assert_eq!(
+            resolver
+                .resolve_homeserver(&key.public_key(), None)
+                .unwrap(),
+            url
+        );
+
+        // Restored from disk by a new process, the packet is not published on its DHT
+        let cache = ResolverCache::new().with_packet_store(PacketStore::open(dir.path()).unwrap());
+        let mut resolver = Resolver::new(None, Some(&other_testnet.bootstrap)).with_cache(cache);
+        assert_eq!(
+            resolver
+                .resolve_homeserver(&key.public_key(), None)
+                .unwrap(),
+            url
+        );
+
+        resolver.invalidate(&key.public_key());
+        assert!(resolver
+            .resolve_homeserver(&key.public_key(), None)
+            .is_err());
+        assert!(PacketStore::open(dir.path())
+            .unwrap()
+            .get(&key.public_key())
+            .is_none());
+    }
+
+    #[test]
+    fn test_resolve_homeserver_read_ahead() {
+        use mainline::dht::Testnet;
+        let testnet = Testnet::new(10);
+
+        let key = Keypair::random();
+        let url = Url::parse("https://datastore.example.com").unwrap();
+        let moved_url = Url::parse("https://other-datastore.example.com").unwrap();
+        Resolver::new(None, Some(&testnet.bootstrap))
+            .publish(&key, &moved_url, None)
+            .unwrap();
+
+        let mut resolver =
+            Resolver::new(None, Some(&testnet.bootstrap)).with_strategy(CacheStrategy::ReadAhead {
+                refresh_before: Duration::from_secs(60),
+            });
+        resolver
+            .cache()
+            .insert(&key.public_key(), url.clone(), Duration::from_secs(30));
+
+        // About to expire, served from the cache and refreshed in the background
+        assert_eq!(
+            resolver
+                .resolve_homeserver(&key.public_key(), None)
+                .unwrap(),
+            url
+        );
+        assert!(wait_for_refresh(
+            resolver.cache(),
+            &key.public_key(),
+            &moved_url
+        ));
+    }
+
+    #[test]
+    fn test_resolve_homeserver_stale_while_revalidate() {
+        use mainline::dht::Testnet;
+        let testnet = Testnet::new(10);
+
+        let key = Keypair::random();
+        let url = Url::parse("https://datastore.example.com").unwrap();
+        let moved_url = Url::parse("https://other-datastore.example.com").unwrap();
+        Resolver::new(None, Some(&testnet.bootstrap))
+            .publish(&key, &moved_url, None)
+            .unwrap();
+
+        let mut resolver = Resolver::new(None, Some(&testnet.bootstrap)).with_strategy(
+            CacheStrategy::StaleWhileRevalidate {
+                max_stale: Duration::from_secs(60),
+            },
+        );
+        resolver
+            .cache()
+            .insert(&key.public_key(), url.clone(), Duration::ZERO);
+
+        // Expired, still served right away while it is refreshed
+        assert_eq!(
+            resolver
+                .resolve_homeserver(&key.public_key(), None)
+                .unwrap(),
+            url
+        );
+        assert!(wait_for_refresh(
+            resolver.cache(),
+            &key.public_key(),
+            &moved_url
+        ));
+        assert_eq!(
+            resolver
+                .resolve_homeserver(&key.public_key(), None)
+                .unwrap(),
+            moved_url
+        );
+    }
+
+    #[test]
+    fn test_resolve_homeserver_failed_refresh() {
+        let key = Keypair::random();
+        let public_key = key.public_key();
+        let url = Url::parse("https://datastore.example.com").unwrap();
+        let mut relay = mockito::Server::new();
+        let relay_url = Url::parse(&relay.url()).unwrap();
+
+        for (strategy, ttl) in [
+            (
+                CacheStrategy::ReadAhead {
+                    refresh_before: Duration::from_secs(60),
+                },
+                Duration::from_secs(30),
+            ),
+            (
+                CacheStrategy::StaleWhileRevalidate {
+                    max_stale: Duration::from_secs(60),
+                },
+                Duration::ZERO,
+            ),
+        ] {
+            let not_found = relay
+                .mock("GET", format!("/{}", public_key.to_z32()).as_str())
+                .with_status(404)
+                .expect_at_least(1)
+                .create();
+            let mut resolver = Resolver::new(Some(&relay_url), None).with_strategy(strategy);
+            resolver.cache().insert(&public_key, url.clone(), ttl);
+
+            assert_eq!(resolver.resolve_homeserver(&public_key, None).unwrap(), url);
+            for _ in 0..100 {
+                if not_found.matched() && resolver.cache().begin_refresh(&public_key) {
+                    break;
+                }
+                sleep(Duration::from_millis(100));
+            }
+            resolver.cache().end_refresh(&public_key);
+
+            // The failed refresh leaves the cached homeserver in place
+            assert_eq!(
+                resolver.cache().entry(&public_key).unwrap().value,
+                Cached::Found(url.clone())
+            );
+            assert_eq!(resolver.resolve_homeserver(&public_key, None).unwrap(), url);
+            not_found.remove();
+        }
+    }
+
+    #[test]
+    fn test_homeserver_ttl() {
+        let key = Keypair::random();
+        let url = Url::parse("https://datastore.example.com").unwrap();
+        let packet = homeserver_packet(&key, &url).unwrap();
+
+        assert_eq!(homeserver_ttl(&packet, &packet), Duration::from_secs(30));
+
+        // Publisher's TTL is not trusted beyond the maximum
+        let mut long_lived = dns::Packet::new_reply(0);
+        long_lived.answers.push(dns::ResourceRecord::new(
+            dns::Name::new("@").unwrap(),
+            dns::CLASS::IN,
+            u32::MAX,
+            dns::rdata::RData::CNAME(dns::Name::new(url.as_str()).unwrap().into()),
+        ));
+        let packet = SignedPacket::from_packet(&key, &long_lived).unwrap();
+        assert_eq!(homeserver_ttl(&packet, &packet), MAX_TTL);
+        assert_eq!(record_ttl(&packet, "@"), MAX_TTL);
+    }
+}
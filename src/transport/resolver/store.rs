@@ -0,0 +1,268 @@
+use super::cache::MAX_TTL;
+use crate::error::DHTError as Error;
+use crate::utils::{now, write_atomic};
+use bytes::Bytes;
+use pkarr::{PublicKey, SignedPacket};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Extension of the files packets are stored in
+static PACKET_EXTENSION: &str = "pkarr";
+
+/// Bytes of a verified signed packet with the time it stays valid
+#[derive(Debug, Clone)]
+struct StoredPacket {
+    packet: Bytes,
+    /// Unix timestamp (seconds) the packet is valid until
+    expires_at: u64,
+}
+
+impl StoredPacket {
+    /// `expires_at` (u64 big endian) followed by the bytes of the signed packet
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.packet.len());
+        bytes.extend_from_slice(&self.expires_at.to_be_bytes());
+        bytes.extend_from_slice(&self.packet);
+        bytes
+    }
+
+    /// Parses the stored packet and the public key it is signed by, verifying its signature.
+    ///
+    /// The stored `expires_at` is not signed, so it is kept to at most [`MAX_TTL`] after the
+    /// packet was signed.
+    fn from_bytes(bytes: Vec<u8>) -> Option<(StoredPacket, PublicKey)> {
+        if bytes.len() < 8 {
+            return None;
+        }
+        let expires_at = u64::from_be_bytes(bytes[..8].try_into().ok()?);
+        let packet = Bytes::from(bytes).slice(8..);
+        let signed_packet = SignedPacket::from_bytes(packet.clone(), true).ok()?;
+        // Packet timestamps are in microseconds
+        let expires_at = expires_at
+            .min((signed_packet.timestamp() / 1_000_000).saturating_add(MAX_TTL.as_secs()));
+
+        Some((
+            StoredPacket { packet, expires_at },
+            signed_packet.public_key().clone(),
+        ))
+    }
+
+    /// Packet parsed again, its signature was verified when it was stored
+    fn signed_packet(&self) -> Option<SignedPacket> {
+        SignedPacket::from_bytes(self.packet.clone(), false).ok()
+    }
+}
+
+/// Keeps resolved signed packets in a directory, so they survive process restarts.
+///
+/// Every packet is stored in its own file named after the public key. Signatures are verified
+/// again when the directory is opened, invalid and expired packets are removed. Clones share the
+/// same packets.
+#[derive(Debug, Clone)]
+pub struct PacketStore {
+    dir: PathBuf,
+    packets: Arc<Mutex<HashMap<String, StoredPacket>>>,
+}
+
+impl PacketStore {
+    /// Opens the directory, creating it if needed, and loads the packets in it
+    pub fn open(dir: impl Into<PathBuf>) -> Result<PacketStore, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| Error::FailedToAccessCache(e.to_string()))?;
+
+        let mut packets = HashMap::new();
+        let now = now();
+        for file in fs::read_dir(&dir).map_err(|e| Error::FailedToAccessCache(e.to_string()))? {
+            let path = match file {
+                Ok(file) => file.path(),
+                Err(_) => continue,
+            };
+            if path.extension().and_then(|ext| ext.to_str()) != Some(PACKET_EXTENSION) {
+                continue;
+            }
+            let key = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(key) => key.to_string(),
+                None => continue,
+            };
+
+            // Packets of other keys, broken or forged ones are dropped as well
+            match fs::read(&path).ok().and_then(StoredPacket::from_bytes) {
+                Some((stored, public_key))
+                    if stored.expires_at > now && public_key.to_z32() == key =>
+                {
+                    packets.insert(key, stored);
+                }
+                _ => {
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+
+        Ok(PacketStore {
+            dir,
+            packets: Arc::new(Mutex::new(packets)),
+        })
+    }
+
+    /// Live packet of the public key, expired packets are removed
+    pub fn get(&self, public_key: &PublicKey) -> Option<SignedPacket> {
+        self.entry(public_key).map(|(packet, _)| packet)
+    }
+
+    /// Stores the packet for `ttl`, replacing the previous packet of its public key
+    pub fn insert(&self, packet: &SignedPacket, ttl: Duration) -> Result<(), Error> {
+        let key = packet.public_key().to_z32();
+        let stored = StoredPacket {
+            packet: packet.as_bytes(),
+            expires_at: now().saturating_add(ttl.as_secs()),
+        };
+
+        write_atomic(&self.path(&key), &stored.to_bytes(), 0o644)
+            .map_err(|e| Error::FailedToAccessCache(e.to_string()))?;
+        self.packets.lock().unwrap().insert(key, stored);
+
+        Ok(())
+    }
+
+    pub fn remove(&self, public_key: &PublicKey) {
+        let key = public_key.to_z32();
+        self.packets.lock().unwrap().remove(&key);
+        let _ = fs::remove_file(self.path(&key));
+    }
+
+    pub fn clear(&self) {
+        let keys: Vec<String> = self
+            .packets
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(k, _)| k)
+            .collect();
+        for key in keys {
+            let _ = fs::remove_file(self.path(&key));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Live packet of the public key with its expiration
+    pub(crate) fn entry(&self, public_key: &PublicKey) -> Option<(SignedPacket, u64)> {
+        let key = public_key.to_z32();
+        let stored = self.packets.lock().unwrap().get(&key).cloned()?;
+
+        if stored.expires_at <= now() {
+            self.remove(public_key);
+            return None;
+        }
+
+        Some((stored.signed_packet()?, stored.expires_at))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.{PACKET_EXTENSION}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+    use crate::transport::resolver::homeserver_packet;
+    use pkarr::Keypair;
+    use reqwest::Url;
+
+    #[test]
+    fn test_packet_store() {
+        let temp_dir = TempDir::new("pdk-packets");
+        let dir = temp_dir.path();
+        let url = Url::parse("https://datastore.example.com").unwrap();
+
+        let key = Keypair::random();
+        let expired_key = Keypair::random();
+        let forged_key = Keypair::random();
+        let packet = homeserver_packet(&key, &url).unwrap();
+
+        let store = PacketStore::open(dir).unwrap();
+        assert!(store.is_empty());
+        store.insert(&packet, Duration::from_secs(60)).unwrap();
+        store
+            .insert(
+                &homeserver_packet(&expired_key, &url).unwrap(),
+                Duration::ZERO,
+            )
+            .unwrap();
+        assert_eq!(
+            store.get(&key.public_key()).unwrap().as_bytes(),
+            packet.as_bytes()
+        );
+        assert!(store.get(&expired_key.public_key()).is_none());
+        // Unbounded TTL saturates instead of overflowing
+        let lasting_key = Keypair::random();
+        store
+            .insert(
+                &homeserver_packet(&lasting_key, &url).unwrap(),
+                Duration::MAX,
+            )
+            .unwrap();
+        assert_eq!(store.entry(&lasting_key.public_key()).unwrap().1, u64::MAX);
+        // The unsigned expiration is clamped when loaded
+        let lasting_packet = store.get(&lasting_key.public_key()).unwrap();
+        assert_eq!(
+            PacketStore::open(dir)
+                .unwrap()
+                .entry(&lasting_key.public_key())
+                .unwrap()
+                .1,
+            lasting_packet.timestamp() / 1_000_000 + MAX_TTL.as_secs()
+        );
+        store.remove(&lasting_key.public_key());
+        // Temporary files are renamed into place
+        assert!(fs::read_dir(dir).unwrap().all(|entry| entry
+            .unwrap()
+            .path()
+            .extension()
+            .is_some_and(|extension| extension == PACKET_EXTENSION)));
+
+        // Valid packet stored under the name of another key
+        fs::copy(
+            store.path(&key.public_key().to_z32()),
+            store.path(&forged_key.public_key().to_z32()),
+        )
+        .unwrap();
+        // Packet with a broken signature
+        let tampered_key = Keypair::random();
+        store
+            .insert(
+                &homeserver_packet(&tampered_key, &url).unwrap(),
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        let tampered_path = store.path(&tampered_key.public_key().to_z32());
+        let mut tampered = fs::read(&tampered_path).unwrap();
+        tampered[8 + 32] ^= 1;
+        fs::write(&tampered_path, &tampered).unwrap();
+        fs::write(dir.join("unrelated.txt"), b"not a packet").unwrap();
+
+        let reopened = PacketStore::open(dir).unwrap();
+        assert_eq!(reopened.len(), 1);
+        assert_eq!(
+            reopened.get(&key.public_key()).unwrap().as_bytes(),
+            packet.as_bytes()
+        );
+        assert!(!reopened.path(&forged_key.public_key().to_z32()).exists());
+        assert!(!tampered_path.exists());
+        assert!(dir.join("unrelated.txt").exists());
+
+        reopened.clear();
+        assert!(PacketStore::open(dir).unwrap().is_empty());
+    }
+}
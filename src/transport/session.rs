@@ -0,0 +1,123 @@
+use crate::utils::now;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Session at the homeserver, as returned by `GET /mvp/session`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    pub users: HashMap<String, UserSession>,
+    #[serde(default)]
+    pub created_at: Option<u64>,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+/// Permissions of a single user within the session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserSession {
+    #[serde(default)]
+    pub permissions: Vec<Permission>,
+}
+
+/// Actions allowed over a repository, `*` stands for all repositories
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Permission {
+    pub repo: String,
+    pub actions: Vec<Action>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Read,
+    Write,
+    Delete,
+    #[serde(other)]
+    Unknown,
+}
+
+static ANY_REPO: &str = "*";
+
+impl Session {
+    /// Parses session from the response body, `null` means there is no active session
+    pub fn parse(body: &[u8]) -> Result<Option<Session>, serde_json::Error> {
+        serde_json::from_slice(body)
+    }
+
+    /// Returns permissions of the user within the session
+    pub fn user(&self, user_id: &str) -> Option<&UserSession> {
+        self.users.get(user_id)
+    }
+
+    /// Checks if the user is allowed to perform the action over the repository
+    pub fn has_permission(&self, user_id: &str, repo_name: &str, action: Action) -> bool {
+        match self.user(user_id) {
+            None => false,
+            Some(user) => user.permissions.iter().any(|permission| {
+                (permission.repo == repo_name || permission.repo == ANY_REPO)
+                    && permission.actions.contains(&action)
+            }),
+        }
+    }
+
+    pub fn expired(&self) -> bool {
+        match self.expires_at {
+            None => false,
+            Some(expires_at) => expires_at <= now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let body = br#"{
+            "users": {
+                "alice": {
+                    "permissions": [
+                        { "repo": "posts", "actions": ["read", "write"] },
+                        { "repo": "*", "actions": ["read", "admin"] }
+                    ]
+                }
+            },
+            "created_at": 1000,
+            "expires_at": 2000
+        }"#;
+
+        let session = Session::parse(body).unwrap().unwrap();
+
+        assert_eq!(session.created_at, Some(1000));
+        assert!(session.expired());
+        assert_eq!(
+            session.user("alice").unwrap().permissions[1].actions,
+            vec![Action::Read, Action::Unknown]
+        );
+
+        assert_eq!(Session::parse(b"null").unwrap(), None);
+        assert!(Session::parse(b"session").is_err());
+    }
+
+    #[test]
+    fn test_has_permission() {
+        let session = Session::parse(
+            br#"{ "users": { "alice": { "permissions": [
+                { "repo": "posts", "actions": ["write"] },
+                { "repo": "*", "actions": ["read"] }
+            ] } } }"#,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert!(!session.expired());
+        assert!(session.has_permission("alice", "posts", Action::Write));
+        assert!(session.has_permission("alice", "posts", Action::Read));
+        assert!(session.has_permission("alice", "likes", Action::Read));
+        assert!(!session.has_permission("alice", "likes", Action::Write));
+        assert!(!session.has_permission("alice", "posts", Action::Delete));
+        assert!(!session.has_permission("bob", "posts", Action::Read));
+    }
+}
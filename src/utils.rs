@@ -1,3 +1,6 @@
+use crate::transport::crypto::random_bytes;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn now() -> u64 {
@@ -6,3 +9,36 @@ pub fn now() -> u64 {
         .expect("Time went backwards")
         .as_secs()
 }
+
+/// Writes next to the target and renames, so a crash never leaves a truncated file behind. The
+/// temporary file is unique, so concurrent writers of the same target don't clobber each other.
+/// `mode` sets the permissions of the file on unix.
+pub fn write_atomic(path: &Path, bytes: &[u8], mode: u32) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut tmp = path.to_path_buf().into_os_string();
+    tmp.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        z32::encode(&random_bytes(8))
+    ));
+    let tmp = PathBuf::from(tmp);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let result = options.open(&tmp).and_then(|mut file| {
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+
+    result
+}